@@ -1,36 +1,407 @@
+use std::path::{Path, PathBuf};
 use clap:: {
     Args,
     Parser,
-    Subcommand
+    Subcommand,
+    ValueEnum
 };
 
 #[derive(Parser, Debug)]
 #[command(version, about, long_about = None)]
 pub(crate) struct TodoArgs {
+    /// Show which tasks would be affected without changing anything.
+    #[arg(long, global = true)]
+    pub(crate) dry_run: bool,
+    /// Don't print the summary and legend below the task list.
+    #[arg(short, long, global = true)]
+    pub(crate) quiet: bool,
+    /// When to use colors and styling.
+    #[arg(long, value_enum, global = true, default_value_t = ColorMode::Auto)]
+    pub(crate) color: ColorMode,
+    /// After changing tasks, also print the whole list. By default only the changes are shown.
+    #[arg(long, global = true, alias = "full-list")]
+    pub(crate) list_after: bool,
+    /// When a task name in remove, done or undone isn't found, offer the closest match.
+    #[arg(long, global = true)]
+    pub(crate) fuzzy: bool,
+    /// How to show times: `relative` (like "3h ago"), `iso` (RFC 3339) or a strftime pattern.
+    #[arg(long, global = true, default_value = "relative")]
+    pub(crate) time_format: String,
     /// The operation to do in the task list.
     #[clap(subcommand)]
     pub(crate) command: Command
 }
 
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub(crate) enum ColorMode {
+    /// Use colors when writing to a terminal, unless NO_COLOR is set.
+    Auto,
+    Always,
+    Never
+}
+
 #[derive(Debug, Subcommand)]
 pub(crate) enum Command {
     /// Add tasks to the task list. Separate tasks with spaces.
-    Add(TasksCommand),
+    #[command(after_help = "Examples:
+  todo add milk eggs              Adds two tasks, \"milk\" and \"eggs\"
+  todo add \"Call the bank\"        Quote names that contain spaces
+  todo add --prepend urgent       Adds the task at the top of the list")]
+    Add(AddCommand),
     /// Remove tasks from the task list. Separate tasks with spaces.
-    Remove(TasksCommand),
+    ///
+    /// Tasks can also be given by their position in `todo list`, like `2` or `2-4`.
+    Remove(RemoveCommand),
     /// Mark tasks in the task list as done.
-    Done(TasksCommand),
+    ///
+    /// Tasks can also be given by their position in `todo list`, like `2` or `2-4`.
+    #[command(after_help = "Examples:
+  todo done milk eggs             Marks two tasks as done
+  todo done 2-4                   Marks the 2nd to 4th tasks as done
+  todo done --matching report -y  Marks every pending task containing \"report\"")]
+    Done(DoneCommand),
     /// Mark tasks in the list as undone.
-    Undone(TasksCommand),
+    ///
+    /// Tasks can also be given by their position in `todo list`, like `2` or `2-4`.
+    Undone(DoneCommand),
     /// Remove all tasks. Please note that this is irreversible.
-    Clear,
+    #[command(after_help = "Examples:
+  todo clear                      Asks, then removes every task
+  todo clear --done               Removes only the tasks that are done
+  todo clear --done --before 2024-01-01 --yes
+                                  Removes tasks done before 2024 without asking")]
+    Clear(ClearCommand),
+    /// Move all done tasks to the archive instead of deleting them.
+    Archive(ArchiveCommand),
+    /// Edit the task list in $EDITOR, one task per line. Prefix done tasks with `[x]`.
+    Edit,
+    /// Mark tasks as not done and move them to the top of the list.
+    Bump(TasksCommand),
+    /// Pin tasks so they are always listed first, whatever the sort.
+    ///
+    /// Tasks can also be given by their position in `todo list`, like `2` or `2-4`.
+    Pin(TasksCommand),
+    /// Unpin tasks so they are listed in their usual place again.
+    ///
+    /// Tasks can also be given by their position in `todo list`, like `2` or `2-4`.
+    Unpin(TasksCommand),
+    /// Rename a task, keeping its place, state and notes.
+    ///
+    /// With --regex, FROM is a pattern and TO its replacement (`$1` for groups), applied to every
+    /// task name. The renames are previewed, pass --yes to apply them.
+    Rename(RenameCommand),
+    /// Exchange the positions of two tasks in the list.
+    Swap(SwapCommand),
+    /// Add a pending copy of a task, keeping its category and blockers.
+    Copy(CopyCommand),
+    /// Print the number of tasks and nothing else. Counts pending tasks by default.
+    Count(CountCommand),
+    /// Print the path of the file the tasks are saved in.
+    DataPath(DataPathCommand),
+    /// Print how long each done task took from being added to being marked done.
+    Report,
+    /// Add a note to a task's running log of notes.
+    Note(NoteCommand),
+    /// Show a task with its notes, oldest first.
+    Show(ShowCommand),
+    /// Upgrade a todos.json from an older version, keeping a backup of the original.
+    Migrate,
+    /// Write every task with all its fields to stdout or a file, for backups.
+    Export(ExportCommand),
+    /// Add the tasks from a file written by `export`. Tasks already in the list are skipped.
+    Import(ImportCommand),
+    /// Print the first task in the list.
+    First(EndCommand),
+    /// Print the last task in the list, which is usually the one added most recently.
+    Last(EndCommand),
+    /// Print the first pending task that isn't blocked, i.e. what to do now. Pinned tasks come first.
+    Next(NextCommand),
     /// List all tasks. Tasks marked done are shown with a strike-through.
-    List
+    List(ListCommand)
 }
 
 #[derive(Debug, Args)]
 pub(crate) struct TasksCommand {
-    /// The tasks to add/remove/mark done
+    /// The tasks to act on
     #[arg(num_args = 1..)]
     pub tasks: Vec<String>
-}
\ No newline at end of file
+}
+
+#[derive(Debug, Args)]
+pub(crate) struct RenameCommand {
+    /// The task to rename, or the pattern with --regex
+    pub from: String,
+    /// The new name, or the replacement with --regex
+    pub to: String,
+    /// Rename every task matching FROM as a regular expression.
+    #[arg(long)]
+    pub regex: bool,
+    /// Apply the --regex renames instead of only previewing them.
+    #[arg(short, long, requires = "regex")]
+    pub yes: bool
+}
+
+#[derive(Debug, Args)]
+pub(crate) struct SwapCommand {
+    /// The first task
+    pub first: String,
+    /// The task to swap it with
+    pub second: String
+}
+
+#[derive(Debug, Args)]
+pub(crate) struct AddCommand {
+    /// The tasks to add
+    #[arg(num_args = 1..)]
+    pub tasks: Vec<String>,
+    /// Add the tasks even if the list would grow past the task limit.
+    #[arg(long)]
+    pub force: bool,
+    /// Put the new tasks in this category. Its color is set in `TODO_CATEGORY_COLORS`.
+    #[arg(long)]
+    pub category: Option<String>,
+    /// Add the tasks at the top of the list. Set `TODO_ADD_POSITION=top` to make this the default.
+    #[arg(long, conflicts_with = "append")]
+    pub prepend: bool,
+    /// Add the tasks at the bottom of the list. This is the default.
+    #[arg(long)]
+    pub append: bool,
+    /// Don't allow marking the new tasks done until this task is done. Can be given more than once.
+    #[arg(long, value_name = "TASK")]
+    pub blocked_by: Vec<String>,
+    /// Add the tasks already marked done, e.g. to log finished work.
+    #[arg(long, conflicts_with = "blocked_by")]
+    pub done: bool
+}
+
+#[derive(Debug, Args)]
+pub(crate) struct RemoveCommand {
+    /// The tasks to remove
+    pub tasks: Vec<String>,
+    /// Only remove tasks that are done. Without task names, removes every done task.
+    #[arg(long)]
+    pub done: bool,
+    /// Also remove every task whose name contains this text, ignoring case.
+    #[arg(long, value_name = "TEXT")]
+    pub matching: Option<String>,
+    /// Confirm acting on every task --matching finds. Required when it finds more than one.
+    #[arg(short, long)]
+    pub yes: bool
+}
+
+#[derive(Debug, Args)]
+pub(crate) struct DoneCommand {
+    /// The tasks to mark
+    pub tasks: Vec<String>,
+    /// Mark every task in the list. Cannot be combined with task names.
+    #[arg(long, conflicts_with = "matching")]
+    pub all: bool,
+    /// Also mark every task whose name contains this text, ignoring case. Only tasks that would
+    /// change are matched.
+    #[arg(long, value_name = "TEXT")]
+    pub matching: Option<String>,
+    /// Confirm acting on every task --matching finds. Required when it finds more than one.
+    #[arg(short, long)]
+    pub yes: bool
+}
+
+#[derive(Debug, Args)]
+pub(crate) struct ClearCommand {
+    /// Skip the confirmation prompt. Required when not running in a terminal.
+    #[arg(short, long)]
+    pub yes: bool,
+    /// Only remove tasks that are done.
+    #[arg(long)]
+    pub done: bool,
+    /// With --done, only remove tasks completed before this date (YYYY-MM-DD).
+    /// Tasks without a completion time are kept.
+    #[arg(long, requires = "done")]
+    pub before: Option<String>
+}
+
+#[derive(Debug, Args, Default)]
+pub(crate) struct ListCommand {
+    /// Show at most this many tasks.
+    #[arg(long, allow_negative_numbers = true)]
+    pub limit: Option<i64>,
+    /// Skip this many tasks before showing any.
+    #[arg(long, default_value_t = 0)]
+    pub offset: usize,
+    /// Order the shown tasks by this key. Tasks that compare equal keep their list order.
+    #[arg(long, value_enum)]
+    pub sort: Option<SortKey>,
+    /// Reverse the shown order. Tasks that compare equal still keep their list order.
+    #[arg(long)]
+    pub reverse: bool,
+    /// Only show tasks that are not done.
+    #[arg(long, conflicts_with = "done")]
+    pub pending: bool,
+    /// Only show tasks that are done.
+    #[arg(long)]
+    pub done: bool,
+    /// Print stable tab-separated `done<TAB>name<TAB>id` lines for scripts.
+    #[arg(long)]
+    pub porcelain: bool,
+    /// Only show tasks on or after this date (YYYY-MM-DD).
+    #[arg(long)]
+    pub since: Option<String>,
+    /// Only show tasks on or before this date (YYYY-MM-DD).
+    #[arg(long)]
+    pub until: Option<String>,
+    /// Which date --since and --until look at. Tasks without that date are hidden.
+    #[arg(long, value_enum, default_value_t = DateField::Completed)]
+    pub by: DateField,
+    /// Write the list to this file instead of stdout, without colors.
+    #[arg(long, short)]
+    pub output: Option<PathBuf>,
+    /// Keep running and redraw the list whenever it changes. Stop with Ctrl-C.
+    #[arg(long, conflicts_with = "output")]
+    pub watch: bool
+}
+
+#[derive(Debug, Clone, Copy, Default, ValueEnum)]
+pub(crate) enum DateField {
+    /// When the task was added.
+    Created,
+    /// When the task was marked done.
+    #[default]
+    Completed
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub(crate) enum SortKey {
+    /// Alphabetically, ignoring case.
+    Name,
+    /// Oldest first. Tasks from older versions have no creation time and come before all others.
+    Created,
+    /// Pending tasks first, then done tasks.
+    Done
+}
+
+#[derive(Debug, Args)]
+pub(crate) struct ArchiveCommand {
+    /// Show the archived tasks instead of archiving.
+    #[arg(long)]
+    pub list: bool
+}
+
+#[derive(Debug, Args)]
+#[group(multiple = false)]
+pub(crate) struct CountCommand {
+    /// Count tasks that are not done. This is the default.
+    #[arg(long)]
+    pub pending: bool,
+    /// Count tasks that are done.
+    #[arg(long)]
+    pub done: bool,
+    /// Count all tasks.
+    #[arg(long)]
+    pub all: bool
+}
+
+#[derive(Debug, Args)]
+pub(crate) struct CopyCommand {
+    /// The task to copy
+    pub task: String,
+    /// The name of the copy. Defaults to "<task> (copy)".
+    pub new_name: Option<String>
+}
+
+#[derive(Debug, Args)]
+pub(crate) struct NoteCommand {
+    /// The task to add the note to
+    pub task: String,
+    /// The note. Words are joined with spaces, so quotes are optional.
+    #[arg(num_args = 1.., required = true)]
+    pub text: Vec<String>
+}
+
+#[derive(Debug, Args)]
+pub(crate) struct ShowCommand {
+    /// The task to show
+    pub task: String
+}
+
+#[derive(Debug, Args)]
+pub(crate) struct ExportCommand {
+    /// The file format. Defaults to the one matching the --output extension, or JSON for stdout.
+    #[arg(long, value_enum)]
+    pub format: Option<Format>,
+    /// Write to this file instead of stdout.
+    #[arg(long, short)]
+    pub output: Option<PathBuf>
+}
+
+#[derive(Debug, Args)]
+pub(crate) struct ImportCommand {
+    /// The file to import
+    pub file: PathBuf,
+    /// The file format. Defaults to the one matching the file extension.
+    #[arg(long, value_enum)]
+    pub format: Option<Format>,
+    /// Import the tasks even if the list would grow past the task limit.
+    #[arg(long)]
+    pub force: bool
+}
+
+#[derive(Debug, Clone, Copy, ValueEnum)]
+pub(crate) enum Format {
+    /// Indented JSON with every task field, readable by `import`.
+    Json
+}
+
+impl Format {
+    /// Picks the format matching a file's extension, ignoring case.
+    pub(crate) fn from_path(path: &Path) -> Option<Format> {
+        let extension = path.extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "json" => Some(Format::Json),
+            _ => None
+        }
+    }
+}
+
+#[derive(Debug, Args)]
+pub(crate) struct EndCommand {
+    /// Print this many tasks instead of one.
+    #[arg(long, default_value_t = 1)]
+    pub count: usize
+}
+
+#[derive(Debug, Args)]
+pub(crate) struct NextCommand {
+    /// Pick by this order instead of the list order, like `list --sort`.
+    #[arg(long, value_enum)]
+    pub sort: Option<SortKey>
+}
+
+#[derive(Debug, Args)]
+pub(crate) struct DataPathCommand {
+    /// Also open the folder containing the file.
+    #[arg(long)]
+    pub open: bool
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use clap::CommandFactory;
+
+    #[test]
+    fn formats_come_from_the_extension() {
+        assert!(matches!(Format::from_path(Path::new("tasks.json")), Some(Format::Json)));
+        assert!(matches!(Format::from_path(Path::new("backup/TASKS.JSON")), Some(Format::Json)));
+        assert!(Format::from_path(Path::new("tasks.csv")).is_none());
+        assert!(Format::from_path(Path::new("tasks.md")).is_none());
+        assert!(Format::from_path(Path::new("tasks")).is_none());
+    }
+
+
+    #[test]
+    fn add_help_shows_the_examples() {
+        let mut command = TodoArgs::command();
+        let help = command.find_subcommand_mut("add").unwrap().render_long_help().to_string();
+        assert!(help.contains("Examples:"));
+        assert!(help.contains("todo add \"Call the bank\""));
+    }
+}
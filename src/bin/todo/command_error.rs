@@ -0,0 +1,133 @@
+use std::{fmt, io, path::PathBuf};
+use console::Style;
+
+#[derive(Debug)]
+pub(crate) enum CommandError {
+    /// A confirmation prompt was needed, but there is no terminal to show it on.
+    ConfirmationRequired,
+    /// The list limit was zero or negative.
+    InvalidLimit,
+    /// No task matched the given name, position or range.
+    TaskNotFound(String),
+    /// A task name was empty or only whitespace.
+    EmptyTaskName,
+    /// A task name had more than `max` characters.
+    TaskNameTooLong { name: String, max: usize },
+    /// Two arguments were given that cannot be used together.
+    ConflictingArguments(&'static str, &'static str),
+    /// `remove --done` was given a task that is not done.
+    TaskNotDone(String),
+    /// Adding the tasks would take the list past the soft cap.
+    TaskLimitReached { max: usize },
+    /// A date argument was not in YYYY-MM-DD form.
+    InvalidDate(String),
+    /// A command that needs two different tasks was given the same one twice.
+    SameTask(String),
+    /// A note was empty or only whitespace.
+    EmptyNote,
+    /// A task with this name is already in the list.
+    TaskExists(String),
+    /// A `--regex` pattern didn't compile.
+    InvalidRegex(String),
+    /// No `--format` was given and the file extension doesn't name a known format.
+    UnknownFormat(PathBuf),
+    /// A task can't be marked done because `by` is still pending.
+    Blocked { name: String, by: String },
+    /// A file or process operation failed. `what` completes "Unable to ...".
+    Io { what: String, err: io::Error },
+}
+
+impl fmt::Display for CommandError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            CommandError::ConfirmationRequired => {
+                write!(f, "Not running in a terminal, pass --yes to confirm.")
+            }
+            CommandError::InvalidLimit => write!(f, "The limit must be at least 1."),
+            CommandError::TaskNotFound(task) => write!(f, "Unable to find task: {}", task),
+            CommandError::EmptyTaskName => write!(f, "Task names cannot be empty."),
+            CommandError::TaskNameTooLong { name, max } => {
+                let mut preview: String = name.chars().take(30).collect();
+                if preview.len() < name.len() {
+                    preview.push_str("...");
+                }
+                write!(f, "Task name \"{}\" is longer than {} characters.", preview, max)
+            }
+            CommandError::ConflictingArguments(first, second) => {
+                write!(f, "{} cannot be used together with {}.", first, second)
+            }
+            CommandError::TaskNotDone(task) => write!(f, "Task is not done: {}", task),
+            CommandError::TaskLimitReached { max } => {
+                write!(f, "The task list is limited to {} tasks, pass --force to add more.", max)
+            }
+            CommandError::InvalidDate(date) => write!(f, "Invalid date, expected YYYY-MM-DD: {}", date),
+            CommandError::SameTask(task) => write!(f, "Both tasks are the same: {}", task),
+            CommandError::EmptyNote => write!(f, "Notes cannot be empty."),
+            CommandError::TaskExists(task) => write!(f, "Task already exists: {}", task),
+            CommandError::InvalidRegex(err) => write!(f, "Invalid regex: {}", err),
+            CommandError::UnknownFormat(path) => {
+                write!(f, "Unable to tell the format of {} from its extension, only JSON (.json) is supported.", path.display())
+            }
+            CommandError::Blocked { name, by } => {
+                write!(f, "Task \"{}\" is blocked by \"{}\", which is not done yet.", name, by)
+            }
+            CommandError::Io { what, err } => write!(f, "Unable to {}: {}", what, err),
+        }
+    }
+}
+
+impl CommandError {
+    /// Wraps an IO error for use with `map_err`, e.g. `.map_err(CommandError::io("read the archive"))`.
+    pub(crate) fn io(what: impl Into<String>) -> impl FnOnce(io::Error) -> CommandError {
+        let what = what.into();
+        return move |err| CommandError::Io { what, err }
+    }
+
+    /// A suggestion for fixing the error, shown under it.
+    pub(crate) fn hint(&self) -> Option<String> {
+        match self {
+            CommandError::TaskNotFound(_) => Some("run `todo list` to see the task names and positions".to_string()),
+            CommandError::TaskNameTooLong { .. } => Some("set TODO_MAX_NAME_LENGTH to raise the limit".to_string()),
+            CommandError::TaskLimitReached { .. } => Some("`todo archive` moves done tasks out of the list".to_string()),
+            CommandError::TaskExists(_) => Some("pick another name, or `todo rename` the existing task".to_string()),
+            CommandError::Blocked { by, .. } => Some(format!("mark \"{}\" done first", by)),
+            _ => None
+        }
+    }
+
+    /// Prints the error to stderr with a red "error:" prefix and, if there is one, a yellow hint.
+    pub(crate) fn handle(&self) {
+        eprintln!("{} {}", Style::new().red().bold().for_stderr().apply_to("error:"), self);
+        if let Some(hint) = self.hint() {
+            eprintln!("{} {}", Style::new().yellow().for_stderr().apply_to("hint:"), hint);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn io_errors_say_what_failed() {
+        let err = CommandError::io("read the archive")(io::Error::new(io::ErrorKind::NotFound, "no such file"));
+        assert_eq!(err.to_string(), "Unable to read the archive: no such file");
+    }
+
+
+    #[test]
+    fn unknown_formats_name_the_supported_ones() {
+        let err = CommandError::UnknownFormat(PathBuf::from("tasks.csv"));
+        assert_eq!(err.to_string(), "Unable to tell the format of tasks.csv from its extension, only JSON (.json) is supported.");
+    }
+
+
+    #[test]
+    fn hints_suggest_a_fix() {
+        assert_eq!(CommandError::TaskNotFound("a".to_string()).hint().as_deref(), Some("run `todo list` to see the task names and positions"));
+        let blocked = CommandError::Blocked { name: "b".to_string(), by: "a".to_string() };
+        assert_eq!(blocked.hint().as_deref(), Some("mark \"a\" done first"));
+        assert_eq!(CommandError::TaskLimitReached { max: 3 }.hint().as_deref(), Some("`todo archive` moves done tasks out of the list"));
+        assert_eq!(CommandError::EmptyTaskName.hint(), None);
+    }
+}
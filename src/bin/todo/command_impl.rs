@@ -0,0 +1,1652 @@
+use std::{env, fs, io, process};
+use std::cmp::Ordering;
+use std::path::{Path, PathBuf};
+use chrono::{DateTime, Local, NaiveDate, Utc};
+use regex::Regex;
+use crate::args::{AddCommand,ArchiveCommand,ClearCommand,CopyCommand,CountCommand,DataPathCommand,DateField,DoneCommand,EndCommand,ExportCommand,Format,ImportCommand,ListCommand,NextCommand,NoteCommand,RemoveCommand,RenameCommand,ShowCommand,SortKey,SwapCommand,TasksCommand};
+use crate::command_error::CommandError;
+use crate::fuzzy::closest_match;
+use crate::db::{ArchivedTask,NoteEntry,SaveData,Task,get_index,pending_blocker};
+
+/// What a command did, for the renderer to report.
+pub(crate) enum CommandOutcome {
+    /// Nothing to report besides the updated task list.
+    Nothing,
+    /// The command needs task names, but none were given.
+    NoTasksSpecified,
+    /// Tasks were added. `existing` holds the names that were already in the list.
+    Added { existing: Vec<String> },
+    /// A dry run found these tasks to remove.
+    WouldRemove(Vec<String>),
+    /// The user declined the confirmation prompt.
+    Aborted,
+    /// `--matching` found these tasks, but acting on more than one needs `--yes`.
+    /// `action` is what `--yes` would do, like "remove them".
+    UnconfirmedMatches { names: Vec<String>, action: &'static str },
+    /// A dry run found these tasks to archive.
+    WouldArchive(Vec<String>),
+    /// This many tasks were archived.
+    Archived(usize),
+    /// The contents of the archive, for `archive --list`.
+    ArchiveList(Vec<ArchivedTask>),
+    /// `todo edit` made no changes, either because the editor failed or
+    /// because the file was left as it was.
+    EditUnchanged { editor_failed: bool },
+    /// `todo edit` changed the list.
+    Edited { added: usize, removed: usize, toggled: usize },
+    /// The result of `todo count`.
+    Count(usize),
+    /// The path of the data file, for `todo data-path`.
+    DataPath(PathBuf),
+    /// Tasks from one end of the list, for `todo first` and `todo last`.
+    Ends(Vec<Task>),
+    /// The task to do now, for `todo next`. `None` when nothing is left to do.
+    Next(Option<String>),
+    /// The done tasks, for `todo report`.
+    Report(Vec<Task>),
+    /// `rename --regex` would rename or did rename these `(old, new)` names, and
+    /// skipped `conflicts` whose new name was taken.
+    Renames { renames: Vec<(String, String)>, conflicts: Vec<(String, String)>, applied: bool },
+    /// `todo migrate` upgraded this many tasks and backed the old file up at `backup`.
+    /// `backup` is `None` when there was nothing to migrate.
+    Migrated { count: usize, backup: Option<PathBuf> },
+    /// A task and its notes, for `todo show`.
+    Show(Task),
+    /// The exported file contents, for `todo export` without `--output`.
+    Export(String),
+    /// `todo export --output` wrote this many tasks to the file.
+    Exported { count: usize, path: PathBuf },
+    /// `todo import` added `added` tasks and skipped `skipped` that were already in the list.
+    Imported { added: usize, skipped: usize },
+}
+
+/// The part of the task list that `list` shows.
+pub(crate) struct TaskPage<'a> {
+    /// The shown tasks, each with its index in the saved list.
+    pub tasks: Vec<(usize, &'a Task)>,
+    /// Index of the first task in `tasks` among the matching tasks.
+    pub start: usize,
+    /// Number of tasks left after filtering, before paging.
+    pub matching: usize,
+    /// Length of the whole list.
+    pub total: usize,
+    /// Number of done tasks in the whole list.
+    pub done_total: usize,
+    /// Whether `--limit` or `--offset` was given.
+    pub paged: bool,
+    /// Saved-list indices of the shown tasks that wait on a pending blocker.
+    pub blocked: Vec<usize>,
+    /// The 1-based position of each shown task, as accepted by `remove`, `done` and `pin`.
+    pub positions: Vec<usize>,
+}
+
+const DEFAULT_MAX_TASK_NAME_LENGTH: usize = 256;
+
+/// The longest allowed task name in characters, overridable with `TODO_MAX_NAME_LENGTH`.
+fn max_task_name_length() -> usize {
+    return env::var("TODO_MAX_NAME_LENGTH").ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_MAX_TASK_NAME_LENGTH)
+}
+
+const DEFAULT_MAX_TASKS: usize = 10_000;
+
+/// The soft cap on the number of tasks, overridable with `TODO_MAX_TASKS`.
+fn max_tasks() -> usize {
+    return env::var("TODO_MAX_TASKS").ok()
+        .and_then(|value| value.parse().ok())
+        .unwrap_or(DEFAULT_MAX_TASKS)
+}
+
+/// Whether `add` puts new tasks at the top: `--prepend` or `--append` if given,
+/// otherwise `TODO_ADD_POSITION` (`top` or `bottom`, default `bottom`).
+fn adds_at_top(command: &AddCommand) -> bool {
+    if command.prepend || command.append {
+        return command.prepend
+    }
+    return env::var("TODO_ADD_POSITION").is_ok_and(|position| position.trim().eq_ignore_ascii_case("top"))
+}
+
+/// Trims a task name, rejecting names that are empty, only whitespace or too long.
+fn validate_task_name(name: &str) -> Result<String, CommandError> {
+    let name = name.trim();
+    if name.is_empty() {
+        return Err(CommandError::EmptyTaskName)
+    }
+    let max = max_task_name_length();
+    if name.chars().count() > max {
+        return Err(CommandError::TaskNameTooLong { name: name.to_string(), max })
+    }
+    return Ok(name.to_string())
+}
+
+pub(crate) fn handle_add(data: &mut SaveData, command: &AddCommand) -> Result<CommandOutcome, CommandError> {
+    if command.tasks.is_empty() {
+        return Ok(CommandOutcome::NoTasksSpecified)
+    }
+
+    let names = command.tasks.iter()
+        .map(|task| validate_task_name(task))
+        .collect::<Result<Vec<String>, CommandError>>()?;
+
+    if !command.force {
+        let max = max_tasks();
+        // A name given twice is only added once.
+        let mut new_names: Vec<&String> = names.iter()
+            .filter(|name| get_index(data.get_tasks(), name).is_none())
+            .collect();
+        new_names.sort_unstable();
+        new_names.dedup();
+        if data.get_tasks().len() + new_names.len() > max {
+            return Err(CommandError::TaskLimitReached { max })
+        }
+    }
+
+    if let Some(missing) = command.blocked_by.iter()
+        .find(|blocker| get_index(data.get_tasks(), blocker).is_none() && !names.contains(blocker)) {
+        return Err(CommandError::TaskNotFound(missing.clone()))
+    }
+
+    // Prepended tasks keep the order they were given in.
+    let mut insert_at = if adds_at_top(command) { Some(0) } else { None };
+    let mut existing = Vec::new();
+    for name in names {
+        let option = get_index(data.get_tasks(), &name);
+        match option {
+            Some(_) => existing.push(name),
+            None => {
+                let mut task_obj: Task = Task::new(name);
+                task_obj.category = command.category.clone();
+                task_obj.set_done(command.done);
+                task_obj.blocked_by = command.blocked_by.iter()
+                    .filter(|blocker| **blocker != task_obj.name)
+                    .cloned()
+                    .collect();
+                match insert_at.as_mut() {
+                    Some(index) => {
+                        data.add_task_at(task_obj, *index);
+                        *index += 1;
+                    }
+                    None => data.add_task(task_obj)
+                }
+            }
+        }
+    }
+    return Ok(CommandOutcome::Added { existing })
+}
+
+/// Saved-list indices in the order a plain `todo list` shows them: pinned tasks
+/// first, otherwise in list order. Task positions count in this order.
+fn listed_order(tasks: &[Task]) -> Vec<usize> {
+    let mut order: Vec<usize> = (0..tasks.len()).collect();
+    order.sort_by_key(|index| !tasks[*index].pinned);
+    return order
+}
+
+/// Parses a task position (`3`) or an inclusive range of positions (`2-4`).
+/// Positions start at 1, matching the order tasks are listed in.
+fn parse_range(query: &str) -> Option<(usize, usize)> {
+    match query.split_once('-') {
+        Some((first, last)) => Some((first.parse().ok()?, last.parse().ok()?)),
+        None => {
+            let position = query.parse().ok()?;
+            Some((position, position))
+        }
+    }
+}
+
+/// Asks the user a yes/no question, returning whether they agreed. Handlers are
+/// given one instead of prompting themselves, see `render::confirm`.
+pub(crate) type Ask<'a> = &'a dyn Fn(&str) -> bool;
+
+/// Uses `ask` to check whether the closest task name was meant.
+fn fuzzy_resolve(tasks: &[Task], query: &str, ask: Ask) -> Option<usize> {
+    let name = closest_match(query, tasks.iter().map(|task| task.name.as_str()))?;
+    if !ask(&format!("Did you mean '{}'?", name)) {
+        return None
+    }
+    return get_index(tasks, &name.to_string())
+}
+
+/// Resolves task names, positions and ranges to indices into `tasks`.
+/// Exact name matches take precedence over positions. With `fuzzy`, an unknown
+/// name can be resolved to a similar one if it says yes. The returned indices
+/// are sorted and deduplicated.
+fn resolve_tasks(tasks: &[Task], queries: &[String], fuzzy: Option<Ask>) -> Result<Vec<usize>, CommandError> {
+    let order = listed_order(tasks);
+    let mut indices = Vec::new();
+    for query in queries {
+        if let Some(index) = get_index(tasks, query) {
+            indices.push(index);
+            continue;
+        }
+        match parse_range(query) {
+            Some((first, last)) if first >= 1 && first <= last && last <= tasks.len() => {
+                indices.extend(&order[first - 1..last]);
+            }
+            _ => match fuzzy.and_then(|ask| fuzzy_resolve(tasks, query, ask)) {
+                Some(index) => indices.push(index),
+                None => return Err(CommandError::TaskNotFound(query.clone()))
+            }
+        }
+    }
+    indices.sort_unstable();
+    indices.dedup();
+    return Ok(indices)
+}
+
+/// Finds the names of tasks containing `text`, ignoring case, among those `eligible` accepts.
+/// Callers need `--yes` to act on more than one, see `CommandOutcome::UnconfirmedMatches`.
+fn expand_matching(tasks: &[Task], text: &str, eligible: impl Fn(&Task) -> bool) -> Result<Vec<String>, CommandError> {
+    let needle = text.to_lowercase();
+    let names: Vec<String> = tasks.iter()
+        .filter(|task| eligible(task) && task.name.to_lowercase().contains(&needle))
+        .map(|task| task.name.clone())
+        .collect();
+    if names.is_empty() {
+        return Err(CommandError::TaskNotFound(text.to_string()))
+    }
+    return Ok(names)
+}
+
+pub(crate) fn handle_remove(data: &mut SaveData, command: &RemoveCommand, dry_run: bool, fuzzy: Option<Ask>) -> Result<CommandOutcome, CommandError> {
+    let tasks = data.get_tasks();
+    let mut queries = command.tasks.clone();
+    if let Some(text) = &command.matching {
+        let names = expand_matching(tasks, text, |task| task.done || !command.done)?;
+        // A dry run changes nothing, so there's nothing to confirm.
+        if names.len() > 1 && !command.yes && !dry_run {
+            return Ok(CommandOutcome::UnconfirmedMatches { names, action: "remove them" })
+        }
+        queries.extend(names);
+    }
+
+    let indices: Vec<usize> = if queries.is_empty() {
+        if !command.done {
+            return Ok(CommandOutcome::NoTasksSpecified)
+        }
+        (0..tasks.len()).filter(|index| tasks[*index].done).collect()
+    } else {
+        let indices = resolve_tasks(tasks, &queries, fuzzy)?;
+        if command.done {
+            if let Some(index) = indices.iter().find(|index| !tasks[**index].done) {
+                return Err(CommandError::TaskNotDone(tasks[*index].name.clone()))
+            }
+        }
+        indices
+    };
+    if dry_run {
+        let names = indices.iter().map(|index| data.get_tasks()[*index].name.clone()).collect();
+        return Ok(CommandOutcome::WouldRemove(names))
+    }
+
+    // Remove from the back so the remaining indices stay valid.
+    for index in indices.into_iter().rev() {
+        data.remove_task(index);
+    }
+    return Ok(CommandOutcome::Nothing)
+}
+
+pub(crate) fn handle_done_undone(data: &mut SaveData, command: &DoneCommand, done: bool, fuzzy: Option<Ask>) -> Result<CommandOutcome, CommandError> {
+    let mut queries = command.tasks.clone();
+    if let Some(text) = &command.matching {
+        let names = expand_matching(data.get_tasks(), text, |task| task.done != done)?;
+        if names.len() > 1 && !command.yes {
+            let action = if done { "mark them done" } else { "mark them not done" };
+            return Ok(CommandOutcome::UnconfirmedMatches { names, action })
+        }
+        queries.extend(names);
+    }
+
+    let indices = if command.all {
+        if !queries.is_empty() {
+            return Err(CommandError::ConflictingArguments("--all", "task names"))
+        }
+        (0..data.get_tasks().len()).collect()
+    } else if queries.is_empty() {
+        return Ok(CommandOutcome::NoTasksSpecified)
+    } else {
+        resolve_tasks(data.get_tasks(), &queries, fuzzy)?
+    };
+
+    if done {
+        // Tasks marked done by this same command don't block, so `todo done a b` works when a blocks b.
+        let mut after = data.get_tasks().to_vec();
+        for index in &indices {
+            after[*index].done = true;
+        }
+        for index in &indices {
+            if let Some(blocker) = pending_blocker(&after, &after[*index]) {
+                return Err(CommandError::Blocked { name: after[*index].name.clone(), by: blocker.clone() })
+            }
+        }
+    }
+
+    for index in indices {
+        if done {
+            data.mark_task_done(index);
+        } else {
+            data.mark_task_undone(index);
+        }
+    }
+    return Ok(CommandOutcome::Nothing)
+}
+
+pub(crate) fn handle_bump(data: &mut SaveData, command: &TasksCommand) -> Result<CommandOutcome, CommandError> {
+    if command.tasks.is_empty() {
+        return Ok(CommandOutcome::NoTasksSpecified)
+    }
+    if let Some(missing) = command.tasks.iter().find(|task| get_index(data.get_tasks(), task).is_none()) {
+        return Err(CommandError::TaskNotFound(missing.clone()))
+    }
+
+    // Bump in reverse so the tasks end up at the top in the order they were given.
+    for task in command.tasks.iter().rev() {
+        let index = get_index(data.get_tasks(), task).unwrap();
+        data.mark_task_undone(index);
+        data.move_task(index, 0);
+    }
+    return Ok(CommandOutcome::Nothing)
+}
+
+pub(crate) fn handle_pin(data: &mut SaveData, command: &TasksCommand, pinned: bool, fuzzy: Option<Ask>) -> Result<CommandOutcome, CommandError> {
+    if command.tasks.is_empty() {
+        return Ok(CommandOutcome::NoTasksSpecified)
+    }
+    for index in resolve_tasks(data.get_tasks(), &command.tasks, fuzzy)? {
+        data.tasks_mut()[index].pinned = pinned;
+    }
+    return Ok(CommandOutcome::Nothing)
+}
+
+pub(crate) fn handle_rename(data: &mut SaveData, command: &RenameCommand, dry_run: bool) -> Result<CommandOutcome, CommandError> {
+    if command.regex {
+        return handle_rename_regex(data, command, dry_run)
+    }
+    let index = get_index(data.get_tasks(), &command.from)
+        .ok_or_else(|| CommandError::TaskNotFound(command.from.clone()))?;
+    let new_name = validate_task_name(&command.to)?;
+    if new_name != command.from && get_index(data.get_tasks(), &new_name).is_some() {
+        return Err(CommandError::TaskExists(new_name))
+    }
+    data.rename_task(index, new_name);
+    return Ok(CommandOutcome::Nothing)
+}
+
+fn handle_rename_regex(data: &mut SaveData, command: &RenameCommand, dry_run: bool) -> Result<CommandOutcome, CommandError> {
+    let pattern = Regex::new(&command.from).map_err(|err| CommandError::InvalidRegex(err.to_string()))?;
+    let tasks = data.get_tasks();
+
+    let mut planned = Vec::new();
+    for (index, task) in tasks.iter().enumerate() {
+        let new_name = pattern.replace_all(&task.name, command.to.as_str());
+        if new_name != task.name {
+            planned.push((index, validate_task_name(&new_name)?));
+        }
+    }
+
+    // A new name conflicts if a task that keeps its name has it, or if two renames produce it.
+    let is_renamed = |index: usize| planned.iter().any(|(renamed, _)| *renamed == index);
+    let conflicts_with = |index: usize, new_name: &String| {
+        tasks.iter().enumerate().any(|(other, task)| !is_renamed(other) && task.name == *new_name)
+            || planned.iter().any(|(other, name)| *other != index && name == new_name)
+    };
+    let (conflicting, renames): (Vec<_>, Vec<_>) = planned.iter()
+        .cloned()
+        .partition(|(index, new_name)| conflicts_with(*index, new_name));
+    let name_pairs = |list: &[(usize, String)]| -> Vec<(String, String)> {
+        return list.iter().map(|(index, new_name)| (tasks[*index].name.clone(), new_name.clone())).collect()
+    };
+    let (rename_names, conflict_names) = (name_pairs(&renames), name_pairs(&conflicting));
+
+    let applied = command.yes && !dry_run;
+    if applied {
+        data.rename_tasks(renames);
+    }
+    return Ok(CommandOutcome::Renames { renames: rename_names, conflicts: conflict_names, applied })
+}
+
+pub(crate) fn handle_swap(data: &mut SaveData, command: &SwapCommand) -> Result<CommandOutcome, CommandError> {
+    if command.first == command.second {
+        return Err(CommandError::SameTask(command.first.clone()))
+    }
+    let first_index = get_index(data.get_tasks(), &command.first)
+        .ok_or_else(|| CommandError::TaskNotFound(command.first.clone()))?;
+    let second_index = get_index(data.get_tasks(), &command.second)
+        .ok_or_else(|| CommandError::TaskNotFound(command.second.clone()))?;
+
+    data.swap_tasks(first_index, second_index);
+    return Ok(CommandOutcome::Nothing)
+}
+
+pub(crate) fn handle_copy(data: &mut SaveData, command: &CopyCommand) -> Result<CommandOutcome, CommandError> {
+    let index = get_index(data.get_tasks(), &command.task)
+        .ok_or_else(|| CommandError::TaskNotFound(command.task.clone()))?;
+    let new_name = match &command.new_name {
+        Some(name) => validate_task_name(name)?,
+        None => validate_task_name(&format!("{} (copy)", command.task))?
+    };
+    if get_index(data.get_tasks(), &new_name).is_some() {
+        return Err(CommandError::TaskExists(new_name))
+    }
+
+    // Keep the metadata, but the copy is a new pending task.
+    let mut copy = data.get_tasks()[index].clone();
+    copy.name = new_name;
+    copy.created_at = Some(Utc::now());
+    copy.set_done(false);
+    data.add_task(copy);
+    return Ok(CommandOutcome::Nothing)
+}
+
+/// Clears the list. Without `--yes`, `ask` is asked first and returns whether the
+/// user agreed, or an error when nobody can answer.
+pub(crate) fn handle_clear(data: &mut SaveData, command: &ClearCommand, dry_run: bool, ask: impl FnOnce(&str) -> Result<bool, CommandError>) -> Result<CommandOutcome, CommandError> {
+    let before = command.before.as_deref().map(parse_date).transpose()?;
+    let should_remove = |task: &Task| {
+        if !command.done {
+            return true
+        }
+        match (before, task.completed_at) {
+            (None, _) => task.done,
+            (Some(before), Some(completed_at)) => task.done && completed_at.with_timezone(&Local).date_naive() < before,
+            // Without a completion time there's no telling how old the task is, so keep it.
+            (Some(_), None) => false
+        }
+    };
+
+    let names: Vec<String> = data.iter()
+        .filter(|task| should_remove(task))
+        .map(|task| task.name.clone())
+        .collect();
+    if dry_run {
+        return Ok(CommandOutcome::WouldRemove(names))
+    }
+    if names.is_empty() {
+        return Ok(CommandOutcome::Nothing)
+    }
+
+    if !command.yes {
+        let prompt = if command.done {
+            format!("Remove {} done tasks? This cannot be undone.", names.len())
+        } else {
+            format!("Remove all {} tasks? This cannot be undone.", names.len())
+        };
+        if !ask(&prompt)? {
+            return Ok(CommandOutcome::Aborted)
+        }
+    }
+
+    if command.done {
+        data.retain_tasks(|task| !should_remove(task));
+    } else {
+        data.clear_tasks();
+    }
+    return Ok(CommandOutcome::Nothing)
+}
+
+pub(crate) fn handle_archive(data: &mut SaveData, command: &ArchiveCommand, dry_run: bool) -> Result<CommandOutcome, CommandError> {
+    if command.list {
+        let archive = SaveData::load_archive().map_err(CommandError::io("read the archive"))?;
+        return Ok(CommandOutcome::ArchiveList(archive))
+    }
+
+    if dry_run {
+        let names = data.iter()
+            .filter(|task| task.done)
+            .map(|task| task.name.clone())
+            .collect();
+        return Ok(CommandOutcome::WouldArchive(names))
+    }
+
+    let archived_count = data.archive_done().map_err(CommandError::io("write the archive"))?;
+    return Ok(CommandOutcome::Archived(archived_count))
+}
+
+/// Parses one line of the `todo edit` file into a task name and done state.
+/// Returns `None` for blank lines.
+fn parse_edit_line(line: &str) -> Option<(&str, bool)> {
+    let line = line.trim();
+    if line.is_empty() {
+        return None
+    }
+    if let Some(name) = line.strip_prefix("[x]").or_else(|| line.strip_prefix("[X]")) {
+        return Some((name, true))
+    }
+    return Some((line.strip_prefix("[ ]").unwrap_or(line), false))
+}
+
+fn run_editor(path: &Path) -> Result<bool, CommandError> {
+    let default_editor = if cfg!(windows) { "notepad" } else { "vi" };
+    let editor = env::var("EDITOR").unwrap_or_else(|_| default_editor.to_string());
+    let mut parts = editor.split_whitespace();
+    let program = parts.next().unwrap_or(default_editor);
+
+    let status = process::Command::new(program)
+        .args(parts)
+        .arg(path)
+        .status()
+        .map_err(CommandError::io("start the editor"))?;
+    return Ok(status.success())
+}
+
+/// Creates the file `todo edit` opens in the editor. The temp folder is shared,
+/// so the file must be new: an existing file or symlink at the path is never written through.
+fn create_edit_file() -> io::Result<(PathBuf, fs::File)> {
+    let mut options = fs::OpenOptions::new();
+    options.write(true).create_new(true);
+    #[cfg(unix)]
+    std::os::unix::fs::OpenOptionsExt::mode(&mut options, 0o600);
+
+    // The time makes the name hard to guess, retrying covers the rare clash.
+    for _ in 0..100 {
+        let nanos = Utc::now().timestamp_subsec_nanos();
+        let path = env::temp_dir().join(format!("todo-edit-{}-{}.txt", process::id(), nanos));
+        match options.open(&path) {
+            Ok(file) => return Ok((path, file)),
+            Err(err) if err.kind() == io::ErrorKind::AlreadyExists => continue,
+            Err(err) => return Err(err)
+        }
+    }
+    return Err(io::Error::new(io::ErrorKind::AlreadyExists, "no unused temporary file name"))
+}
+
+pub(crate) fn handle_edit(data: &mut SaveData) -> Result<CommandOutcome, CommandError> {
+    let mut contents = String::new();
+    for task in data.get_tasks() {
+        let marker = if task.done { "[x]" } else { "[ ]" };
+        contents.push_str(&format!("{} {}\n", marker, task.name));
+    }
+
+    let (edit_file_path, mut edit_file) = create_edit_file().map_err(CommandError::io("create the edit file"))?;
+    let written = io::Write::write_all(&mut edit_file, contents.as_bytes());
+    drop(edit_file);
+    if let Err(err) = written {
+        let _ = fs::remove_file(&edit_file_path);
+        return Err(CommandError::io("write the edit file")(err))
+    }
+
+    let editor_result = run_editor(&edit_file_path);
+    let edited = fs::read_to_string(&edit_file_path);
+    let _ = fs::remove_file(&edit_file_path);
+
+    if !editor_result? {
+        return Ok(CommandOutcome::EditUnchanged { editor_failed: true })
+    }
+    let edited = edited.map_err(CommandError::io("read the edit file"))?;
+    if edited == contents {
+        return Ok(CommandOutcome::EditUnchanged { editor_failed: false })
+    }
+    return apply_edit(data, &edited)
+}
+
+/// Replaces the list with the edited file's contents, checking them like `add` and `done` would.
+fn apply_edit(data: &mut SaveData, edited: &str) -> Result<CommandOutcome, CommandError> {
+    let old_tasks = data.get_tasks();
+    let mut new_tasks: Vec<Task> = Vec::new();
+    for (name, done) in edited.lines().filter_map(parse_edit_line) {
+        let name = validate_task_name(name)?;
+        if get_index(&new_tasks, &name).is_some() {
+            continue;
+        }
+        let mut task = match get_index(old_tasks, &name) {
+            Some(index) => old_tasks[index].clone(),
+            None => Task::new(name)
+        };
+        task.set_done(done);
+        new_tasks.push(task);
+    }
+
+    // Like `todo done`, tasks marked done in the same edit as their blockers aren't blocked.
+    for task in &new_tasks {
+        let was_done = get_index(old_tasks, &task.name).is_some_and(|index| old_tasks[index].done);
+        if task.done && !was_done {
+            if let Some(blocker) = pending_blocker(&new_tasks, task) {
+                return Err(CommandError::Blocked { name: task.name.clone(), by: blocker.clone() })
+            }
+        }
+    }
+
+    // Swap the new list in, keeping the old one to count the changes.
+    let old_tasks = std::mem::replace(data, SaveData::from_tasks(new_tasks)).into_tasks();
+    let new_tasks = data.get_tasks();
+    let added = new_tasks.iter().filter(|task| get_index(&old_tasks, &task.name).is_none()).count();
+    let removed = old_tasks.iter().filter(|task| get_index(new_tasks, &task.name).is_none()).count();
+    let toggled = new_tasks.iter()
+        .filter(|task| get_index(&old_tasks, &task.name).is_some_and(|index| old_tasks[index].done != task.done))
+        .count();
+    return Ok(CommandOutcome::Edited { added, removed, toggled })
+}
+
+pub(crate) fn handle_count(data: &SaveData, command: &CountCommand) -> Result<CommandOutcome, CommandError> {
+    let tasks = data.get_tasks();
+    let count = if command.all {
+        tasks.len()
+    } else {
+        tasks.iter().filter(|task| task.done == command.done).count()
+    };
+    return Ok(CommandOutcome::Count(count))
+}
+
+pub(crate) fn handle_first(data: &SaveData, command: &EndCommand) -> Result<CommandOutcome, CommandError> {
+    let tasks = data.get_tasks();
+    let end = command.count.min(tasks.len());
+    return Ok(CommandOutcome::Ends(tasks[..end].to_vec()))
+}
+
+pub(crate) fn handle_last(data: &SaveData, command: &EndCommand) -> Result<CommandOutcome, CommandError> {
+    let tasks = data.get_tasks();
+    let start = tasks.len().saturating_sub(command.count);
+    return Ok(CommandOutcome::Ends(tasks[start..].to_vec()))
+}
+
+pub(crate) fn handle_next(data: &SaveData, command: &NextCommand) -> Result<CommandOutcome, CommandError> {
+    // Pick from what `list --pending` would show, so pins and sorting apply the same way.
+    let list_command = ListCommand { pending: true, sort: command.sort, ..ListCommand::default() };
+    let page = handle_list(data, &list_command)?;
+    let next = page.tasks.iter()
+        .find(|(index, _)| !page.blocked.contains(index))
+        .map(|(_, task)| task.name.clone());
+    return Ok(CommandOutcome::Next(next))
+}
+
+pub(crate) fn handle_report(data: &SaveData) -> Result<CommandOutcome, CommandError> {
+    let done = data.iter().filter(|task| task.done).cloned().collect();
+    return Ok(CommandOutcome::Report(done))
+}
+
+pub(crate) fn handle_note(data: &mut SaveData, command: &NoteCommand) -> Result<CommandOutcome, CommandError> {
+    let index = get_index(data.get_tasks(), &command.task)
+        .ok_or_else(|| CommandError::TaskNotFound(command.task.clone()))?;
+    let text = command.text.join(" ").trim().to_string();
+    if text.is_empty() {
+        return Err(CommandError::EmptyNote)
+    }
+    data.tasks_mut()[index].notes.push(NoteEntry {text, added_at: Utc::now()});
+    return Ok(CommandOutcome::Nothing)
+}
+
+pub(crate) fn handle_show(data: &SaveData, command: &ShowCommand) -> Result<CommandOutcome, CommandError> {
+    let index = get_index(data.get_tasks(), &command.task)
+        .ok_or_else(|| CommandError::TaskNotFound(command.task.clone()))?;
+    return Ok(CommandOutcome::Show(data.get_tasks()[index].clone()))
+}
+
+/// The creation time a task from an older version is given, if it has none.
+/// The real one is unknown, so pending tasks get the migration time and done tasks
+/// their completion time. Done tasks without one stay undated rather than
+/// looking as if they were finished before they were added.
+fn migration_date(task: &Task, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    if task.created_at.is_some() {
+        return None
+    }
+    return if task.done { task.completed_at } else { Some(now) }
+}
+
+/// Gives tasks from older versions a creation time, returning how many were dated.
+fn date_undated_tasks(data: &mut SaveData) -> usize {
+    let now = Utc::now();
+    let mut count = 0;
+    for task in data.tasks_mut().iter_mut() {
+        if let Some(created_at) = migration_date(task, now) {
+            task.created_at = Some(created_at);
+            count += 1;
+        }
+    }
+    return count
+}
+
+pub(crate) fn handle_migrate(data: &mut SaveData, dry_run: bool) -> Result<CommandOutcome, CommandError> {
+    let legacy = SaveData::data_file_is_legacy().map_err(CommandError::io("read the task list"))?;
+    let now = Utc::now();
+    let undated = data.iter().filter(|task| migration_date(task, now).is_some()).count();
+    // Running it again finds nothing to do, so the backup of the original isn't overwritten.
+    if !legacy && undated == 0 {
+        return Ok(CommandOutcome::Migrated { count: 0, backup: None })
+    }
+
+    let backup = if dry_run {
+        None
+    } else {
+        Some(SaveData::backup_data_file().map_err(CommandError::io("back up the task list"))?)
+    };
+    let dated = date_undated_tasks(data);
+    // Saving afterwards writes the file in the current envelope format, which
+    // changes every task of a legacy file. Otherwise only the undated ones changed.
+    let count = if legacy { data.get_tasks().len() } else { dated };
+    return Ok(CommandOutcome::Migrated { count, backup })
+}
+
+pub(crate) fn handle_export(data: &SaveData, command: &ExportCommand) -> Result<CommandOutcome, CommandError> {
+    // Without a file there's no extension to go by, so stdout gets JSON.
+    let format = match (command.format, &command.output) {
+        (Some(format), _) => format,
+        (None, Some(path)) => Format::from_path(path).ok_or_else(|| CommandError::UnknownFormat(path.clone()))?,
+        (None, None) => Format::Json
+    };
+    let mut contents = Vec::new();
+    match format {
+        Format::Json => data.export_json(&mut contents)
+    }.map_err(CommandError::io("export the tasks"))?;
+
+    return match &command.output {
+        Some(path) => {
+            fs::write(path, contents).map_err(CommandError::io(format!("write {}", path.display())))?;
+            Ok(CommandOutcome::Exported { count: data.get_tasks().len(), path: path.clone() })
+        }
+        None => Ok(CommandOutcome::Export(String::from_utf8_lossy(&contents).into_owned()))
+    }
+}
+
+pub(crate) fn handle_import(data: &mut SaveData, command: &ImportCommand) -> Result<CommandOutcome, CommandError> {
+    let file = fs::File::open(&command.file)
+        .map_err(CommandError::io(format!("open {}", command.file.display())))?;
+    let format = match command.format {
+        Some(format) => format,
+        None => Format::from_path(&command.file).ok_or_else(|| CommandError::UnknownFormat(command.file.clone()))?
+    };
+    let tasks = match format {
+        Format::Json => SaveData::read_tasks(file)
+    }.map_err(CommandError::io(format!("read {}", command.file.display())))?;
+    return import_tasks(data, tasks, command.force)
+}
+
+/// Merges imported tasks into the list, checking them like `add` does.
+/// Nothing is added unless every task passes.
+fn import_tasks(data: &mut SaveData, tasks: Vec<Task>, force: bool) -> Result<CommandOutcome, CommandError> {
+    // Merge by name, so importing the same backup twice doesn't duplicate anything.
+    let mut new_tasks: Vec<Task> = Vec::new();
+    let mut skipped = 0;
+    for mut task in tasks {
+        task.name = validate_task_name(&task.name)?;
+        if get_index(data.get_tasks(), &task.name).is_some() || get_index(&new_tasks, &task.name).is_some() {
+            skipped += 1;
+        } else {
+            new_tasks.push(task);
+        }
+    }
+
+    let max = max_tasks();
+    if !force && data.get_tasks().len() + new_tasks.len() > max {
+        return Err(CommandError::TaskLimitReached { max })
+    }
+    for task in &new_tasks {
+        if let Some(missing) = task.blocked_by.iter()
+            .find(|blocker| get_index(data.get_tasks(), blocker).is_none() && get_index(&new_tasks, blocker).is_none()) {
+            return Err(CommandError::TaskNotFound(missing.clone()))
+        }
+    }
+
+    let added = new_tasks.len();
+    for task in new_tasks {
+        data.add_task(task);
+    }
+    return Ok(CommandOutcome::Imported { added, skipped })
+}
+
+/// Opens a folder in the platform's file manager.
+fn open_folder(path: &Path) -> io::Result<()> {
+    let opener = if cfg!(windows) {
+        "explorer"
+    } else if cfg!(target_os = "macos") {
+        "open"
+    } else {
+        "xdg-open"
+    };
+    // Only a failure to start the opener is reported. explorer exits with 1 even when it works.
+    process::Command::new(opener).arg(path).status()?;
+    return Ok(())
+}
+
+pub(crate) fn handle_data_path(command: &DataPathCommand) -> Result<CommandOutcome, CommandError> {
+    let (app_dirs, data_file_path) = SaveData::get_data_paths().map_err(CommandError::io("find the data folder"))?;
+    if command.open {
+        open_folder(&app_dirs.data_dir)
+            .map_err(CommandError::io(format!("open {}", app_dirs.data_dir.display())))?;
+    }
+    return Ok(CommandOutcome::DataPath(data_file_path))
+}
+
+fn parse_date(date: &str) -> Result<NaiveDate, CommandError> {
+    return NaiveDate::parse_from_str(date, "%Y-%m-%d")
+        .map_err(|_| CommandError::InvalidDate(date.to_string()))
+}
+
+fn compare_tasks(a: &Task, b: &Task, key: SortKey) -> Ordering {
+    match key {
+        SortKey::Name => a.name.to_lowercase().cmp(&b.name.to_lowercase()),
+        SortKey::Created => a.created_at.cmp(&b.created_at),
+        SortKey::Done => a.done.cmp(&b.done)
+    }
+}
+
+pub(crate) fn handle_list<'a>(data: &'a SaveData, command: &ListCommand) -> Result<TaskPage<'a>, CommandError> {
+    let limit = match command.limit {
+        Some(limit) if limit <= 0 => return Err(CommandError::InvalidLimit),
+        Some(limit) => Some(limit as usize),
+        None => None
+    };
+
+    let since = command.since.as_deref().map(parse_date).transpose()?;
+    let until = command.until.as_deref().map(parse_date).transpose()?;
+
+    let mut tasks: Vec<(usize, &Task)> = data.iter()
+        .enumerate()
+        .filter(|(_, task)| {
+            if command.pending { !task.done } else if command.done { task.done } else { true }
+        })
+        .filter(|(_, task)| {
+            if since.is_none() && until.is_none() {
+                return true
+            }
+            let date = match command.by {
+                DateField::Created => task.created_at,
+                DateField::Completed => task.completed_at
+            };
+            // Both bounds are inclusive and compared as local dates.
+            match date.map(|date| date.with_timezone(&Local).date_naive()) {
+                Some(date) => since.is_none_or(|since| date >= since) && until.is_none_or(|until| date <= until),
+                None => false
+            }
+        })
+        .collect();
+    // The sort is stable, so ties keep their list order. Only the shown
+    // order changes, the saved list is left as it is.
+    if let Some(key) = command.sort {
+        tasks.sort_by(|(_, a), (_, b)| {
+            let ordering = compare_tasks(a, b, key);
+            if command.reverse { ordering.reverse() } else { ordering }
+        });
+    } else if command.reverse {
+        tasks.reverse();
+    }
+    // Stable too, so pinned tasks keep the order chosen above.
+    tasks.sort_by_key(|(_, task)| !task.pinned);
+
+    let start = command.offset.min(tasks.len());
+    let end = match limit {
+        Some(limit) => start.saturating_add(limit).min(tasks.len()),
+        None => tasks.len()
+    };
+
+    let matching = tasks.len();
+    let total = data.get_tasks().len();
+    let done_total = data.iter().filter(|task| task.done).count();
+    let tasks: Vec<(usize, &Task)> = tasks.drain(start..end).collect();
+    let blocked = tasks.iter()
+        .filter(|(_, task)| !task.done && pending_blocker(data.get_tasks(), task).is_some())
+        .map(|(index, _)| *index)
+        .collect();
+    let mut listed_positions = vec![0; total];
+    for (position, index) in listed_order(data.get_tasks()).into_iter().enumerate() {
+        listed_positions[index] = position + 1;
+    }
+    let positions = tasks.iter().map(|(index, _)| listed_positions[*index]).collect();
+    return Ok(TaskPage {
+        tasks,
+        start,
+        matching,
+        total,
+        done_total,
+        paged: limit.is_some() || command.offset > 0,
+        blocked,
+        positions,
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn data_with(names: &[&str]) -> SaveData {
+        return SaveData::from_tasks(names.iter().map(|name| Task::new(*name)).collect())
+    }
+
+    fn names(data: &SaveData) -> Vec<&str> {
+        return data.iter().map(|task| task.name.as_str()).collect()
+    }
+
+    fn queries(queries: &[&str]) -> Vec<String> {
+        return queries.iter().map(|query| query.to_string()).collect()
+    }
+
+    fn add_command(tasks: &[&str]) -> AddCommand {
+        return AddCommand {
+            tasks: queries(tasks),
+            force: false,
+            category: None,
+            prepend: false,
+            // Explicit so the tests don't depend on TODO_ADD_POSITION.
+            append: true,
+            blocked_by: Vec::new(),
+            done: false
+        }
+    }
+
+    fn clear_command() -> ClearCommand {
+        return ClearCommand { yes: false, done: false, before: None }
+    }
+
+    #[test]
+    fn dry_run_clear_leaves_tasks_unchanged() {
+        let mut data = data_with(&["a", "b"]);
+        let outcome = handle_clear(&mut data, &clear_command(), true, |_| panic!("a dry run must not prompt"));
+        assert!(matches!(outcome, Ok(CommandOutcome::WouldRemove(names)) if names == ["a", "b"]));
+        assert_eq!(names(&data), ["a", "b"]);
+    }
+
+    #[test]
+    fn declining_clear_keeps_tasks() {
+        let mut data = data_with(&["a", "b"]);
+        let outcome = handle_clear(&mut data, &clear_command(), false, |_| Ok(false));
+        assert!(matches!(outcome, Ok(CommandOutcome::Aborted)));
+        assert_eq!(names(&data), ["a", "b"]);
+    }
+
+    #[test]
+    fn accepting_clear_removes_tasks() {
+        let mut data = data_with(&["a", "b"]);
+        let outcome = handle_clear(&mut data, &clear_command(), false, |_| Ok(true));
+        assert!(matches!(outcome, Ok(CommandOutcome::Nothing)));
+        assert!(data.get_tasks().is_empty());
+    }
+
+    #[test]
+    fn parse_range_reads_positions_and_ranges() {
+        assert_eq!(parse_range("3"), Some((3, 3)));
+        assert_eq!(parse_range("2-4"), Some((2, 4)));
+        assert_eq!(parse_range("milk"), None);
+        assert_eq!(parse_range("2-"), None);
+        assert_eq!(parse_range("-1"), None);
+    }
+
+    #[test]
+    fn resolve_tasks_mixes_names_positions_and_ranges() {
+        let data = data_with(&["a", "b", "c", "d"]);
+        let indices = resolve_tasks(data.get_tasks(), &queries(&["d", "1", "2-3", "b"]), None);
+        assert_eq!(indices.unwrap(), [0, 1, 2, 3]);
+    }
+
+    #[test]
+    fn resolve_tasks_prefers_names_over_positions() {
+        let data = data_with(&["a", "1"]);
+        assert_eq!(resolve_tasks(data.get_tasks(), &queries(&["1"]), None).unwrap(), [1]);
+    }
+
+    #[test]
+    fn resolve_tasks_rejects_out_of_range_positions() {
+        let data = data_with(&["a", "b", "c"]);
+        for query in ["0", "4", "2-4", "3-2"] {
+            let result = resolve_tasks(data.get_tasks(), &queries(&["a", query]), None);
+            assert!(matches!(result, Err(CommandError::TaskNotFound(token)) if token == query), "{}", query);
+        }
+    }
+
+    #[test]
+    fn positions_count_pinned_tasks_first() {
+        let mut data = data_with(&["a", "b", "c"]);
+        data.tasks_mut()[2].pinned = true;
+
+        let page = handle_list(&data, &ListCommand::default()).unwrap();
+        let shown: Vec<&str> = page.tasks.iter().map(|(_, task)| task.name.as_str()).collect();
+        assert_eq!(shown, ["c", "a", "b"]);
+        assert_eq!(page.positions, [1, 2, 3]);
+
+        assert_eq!(resolve_tasks(data.get_tasks(), &queries(&["1"]), None).unwrap(), [2]);
+        assert_eq!(resolve_tasks(data.get_tasks(), &queries(&["2-3"]), None).unwrap(), [0, 1]);
+    }
+
+    #[test]
+    fn pinned_tasks_stay_first_when_sorted() {
+        let mut data = data_with(&["b", "d", "a", "c"]);
+        data.tasks_mut()[1].pinned = true;
+        data.tasks_mut()[3].pinned = true;
+
+        let command = ListCommand { sort: Some(SortKey::Name), ..ListCommand::default() };
+        let page = handle_list(&data, &command).unwrap();
+        let shown: Vec<&str> = page.tasks.iter().map(|(_, task)| task.name.as_str()).collect();
+        assert_eq!(shown, ["c", "d", "a", "b"]);
+        assert_eq!(page.positions, [2, 1, 4, 3]);
+    }
+
+    #[test]
+    fn remove_resolves_every_position_before_removing() {
+        let mut data = data_with(&["a", "b", "c", "d", "e"]);
+        let command = RemoveCommand { tasks: queries(&["2-3", "5"]), done: false, matching: None, yes: false };
+        handle_remove(&mut data, &command, false, None).unwrap();
+        assert_eq!(names(&data), ["a", "d"]);
+    }
+
+    #[test]
+    fn list_sorts_by_creation_time() {
+        let mut data = data_with(&["new", "old", "older version", "middle"]);
+        let start = Utc::now();
+        let tasks = data.tasks_mut();
+        tasks[0].created_at = Some(start + chrono::TimeDelta::hours(2));
+        tasks[1].created_at = Some(start);
+        tasks[2].created_at = None;
+        tasks[3].created_at = Some(start + chrono::TimeDelta::hours(1));
+
+        let command = ListCommand { sort: Some(SortKey::Created), ..ListCommand::default() };
+        let page = handle_list(&data, &command).unwrap();
+        let shown: Vec<&str> = page.tasks.iter().map(|(_, task)| task.name.as_str()).collect();
+        assert_eq!(shown, ["older version", "old", "middle", "new"]);
+    }
+
+    #[test]
+    fn export_then_import_restores_the_list() {
+        let mut data = data_with(&["a", "b", "c"]);
+        data.mark_task_done(0);
+        let tasks = data.tasks_mut();
+        tasks[1].category = Some("work".to_string());
+        tasks[1].pinned = true;
+        tasks[2].blocked_by = vec!["a".to_string()];
+        tasks[2].notes.push(NoteEntry {text: "call first".to_string(), added_at: Utc::now()});
+
+        let mut exported = Vec::new();
+        data.export_json(&mut exported).unwrap();
+        let mut imported = SaveData::new();
+        let tasks = SaveData::read_tasks(exported.as_slice()).unwrap();
+        let outcome = import_tasks(&mut imported, tasks, false).unwrap();
+
+        assert!(matches!(outcome, CommandOutcome::Imported { added: 3, skipped: 0 }));
+        assert_eq!(imported.get_tasks(), data.get_tasks());
+    }
+
+    #[test]
+    fn import_skips_tasks_already_in_the_list() {
+        let mut data = data_with(&["a"]);
+        let outcome = import_tasks(&mut data, vec![Task::new("a"), Task::new("b"), Task::new("b")], false).unwrap();
+        assert!(matches!(outcome, CommandOutcome::Imported { added: 1, skipped: 2 }));
+        assert_eq!(names(&data), ["a", "b"]);
+    }
+
+    #[test]
+    fn import_rejects_invalid_tasks_without_changes() {
+        let mut blocked = Task::new("c");
+        blocked.blocked_by = vec!["missing".to_string()];
+        let cases = [
+            vec![Task::new("b"), Task::new("")],
+            vec![Task::new("b"), Task::new("   ")],
+            vec![Task::new("x".repeat(DEFAULT_MAX_TASK_NAME_LENGTH + 1))],
+            vec![Task::new("b"), blocked],
+        ];
+        for tasks in cases {
+            let mut data = data_with(&["a"]);
+            assert!(import_tasks(&mut data, tasks, false).is_err());
+            assert_eq!(names(&data), ["a"]);
+        }
+    }
+
+    #[test]
+    fn import_respects_the_task_limit() {
+        let full: Vec<Task> = (0..DEFAULT_MAX_TASKS).map(|number| Task::new(number.to_string())).collect();
+        let mut data = SaveData::from_tasks(full);
+        let result = import_tasks(&mut data, vec![Task::new("one more")], false);
+        assert!(matches!(result, Err(CommandError::TaskLimitReached { max: DEFAULT_MAX_TASKS })));
+        assert_eq!(data.get_tasks().len(), DEFAULT_MAX_TASKS);
+
+        import_tasks(&mut data, vec![Task::new("one more")], true).unwrap();
+        assert_eq!(data.get_tasks().len(), DEFAULT_MAX_TASKS + 1);
+    }
+
+    fn done_command(matching: &str, yes: bool) -> DoneCommand {
+        return DoneCommand { tasks: Vec::new(), all: false, matching: Some(matching.to_string()), yes }
+    }
+
+    #[test]
+    fn matching_one_task_needs_no_confirmation() {
+        let mut data = data_with(&["write report", "buy milk"]);
+        handle_done_undone(&mut data, &done_command("REPORT", false), true, None).unwrap();
+        assert!(data.get_tasks()[0].done && !data.get_tasks()[1].done);
+    }
+
+    #[test]
+    fn matching_several_tasks_needs_yes() {
+        let mut data = data_with(&["write report", "read report", "buy milk"]);
+        let outcome = handle_done_undone(&mut data, &done_command("report", false), true, None).unwrap();
+        assert!(matches!(outcome, CommandOutcome::UnconfirmedMatches { names, .. } if names == ["write report", "read report"]));
+        assert!(data.iter().all(|task| !task.done));
+
+        handle_done_undone(&mut data, &done_command("report", true), true, None).unwrap();
+        let done: Vec<bool> = data.iter().map(|task| task.done).collect();
+        assert_eq!(done, [true, true, false]);
+    }
+
+    #[test]
+    fn matching_skips_tasks_that_would_not_change() {
+        let mut data = data_with(&["write report", "read report"]);
+        data.mark_task_done(1);
+        let outcome = handle_done_undone(&mut data, &done_command("report", false), true, None).unwrap();
+        assert!(matches!(outcome, CommandOutcome::Nothing));
+        assert!(data.iter().all(|task| task.done));
+    }
+
+    #[test]
+    fn migrating_a_legacy_file_dates_every_task_once() {
+        let legacy = r#"[{"name":"buy milk","done":false},{"name":"new","done":false,"created_at":"2024-05-01T12:00:00Z"}]"#;
+        let mut data = SaveData::from_tasks(SaveData::read_tasks(legacy.as_bytes()).unwrap());
+        let created_at = data.get_tasks()[1].created_at;
+
+        assert_eq!(date_undated_tasks(&mut data), 1);
+        assert!(data.iter().all(|task| task.created_at.is_some()));
+        assert_eq!(data.get_tasks()[1].created_at, created_at);
+        assert_eq!(date_undated_tasks(&mut data), 0);
+    }
+
+    #[test]
+    fn migrating_done_tasks_uses_their_completion_time() {
+        let legacy = r#"[{"name":"paid","done":true,"completed_at":"2024-01-01T12:00:00Z"},{"name":"old","done":true}]"#;
+        let mut data = SaveData::from_tasks(SaveData::read_tasks(legacy.as_bytes()).unwrap());
+        let completed_at = data.get_tasks()[0].completed_at;
+
+        assert_eq!(date_undated_tasks(&mut data), 1);
+        assert_eq!(data.get_tasks()[0].created_at, completed_at);
+        assert_eq!(data.get_tasks()[0].time_to_complete(), Some(chrono::TimeDelta::zero()));
+        assert_eq!(data.get_tasks()[1].created_at, None);
+        assert_eq!(date_undated_tasks(&mut data), 0);
+    }
+
+    #[test]
+    fn add_rejects_blank_names() {
+        for name in ["", "   ", "\t", " \t "] {
+            let mut data = data_with(&["a"]);
+            let result = handle_add(&mut data, &add_command(&["b", name]));
+            assert!(matches!(result, Err(CommandError::EmptyTaskName)), "{:?}", name);
+            assert_eq!(names(&data), ["a"]);
+        }
+    }
+
+    #[test]
+    fn add_trims_names() {
+        let mut data = SaveData::new();
+        handle_add(&mut data, &add_command(&["  milk\t"])).unwrap();
+        assert_eq!(names(&data), ["milk"]);
+    }
+
+    #[test]
+    fn name_length_counts_characters_not_bytes() {
+        let longest = "\u{1F389}".repeat(DEFAULT_MAX_TASK_NAME_LENGTH);
+        assert!(longest.len() > DEFAULT_MAX_TASK_NAME_LENGTH);
+        assert_eq!(validate_task_name(&longest).unwrap(), longest);
+
+        let too_long = "\u{1F389}".repeat(DEFAULT_MAX_TASK_NAME_LENGTH + 1);
+        let result = validate_task_name(&too_long);
+        assert!(matches!(result, Err(CommandError::TaskNameTooLong { max: DEFAULT_MAX_TASK_NAME_LENGTH, .. })));
+    }
+
+    fn shown_names<'a>(page: &TaskPage<'a>) -> Vec<&'a str> {
+        return page.tasks.iter().map(|(_, task)| task.name.as_str()).collect()
+    }
+
+    #[test]
+    fn list_sorts_by_name_ignoring_case() {
+        let data = data_with(&["banana", "Apple", "cherry"]);
+        let command = ListCommand { sort: Some(SortKey::Name), ..ListCommand::default() };
+        assert_eq!(shown_names(&handle_list(&data, &command).unwrap()), ["Apple", "banana", "cherry"]);
+    }
+
+    #[test]
+    fn list_sorts_pending_before_done() {
+        let mut data = data_with(&["a", "b", "c", "d"]);
+        data.mark_task_done(0);
+        data.mark_task_done(2);
+        let command = ListCommand { sort: Some(SortKey::Done), ..ListCommand::default() };
+        assert_eq!(shown_names(&handle_list(&data, &command).unwrap()), ["b", "d", "a", "c"]);
+    }
+
+    #[test]
+    fn reversed_sort_keeps_ties_in_list_order() {
+        let mut data = data_with(&["a", "b", "c", "d"]);
+        data.mark_task_done(0);
+        data.mark_task_done(2);
+        let command = ListCommand { sort: Some(SortKey::Done), reverse: true, ..ListCommand::default() };
+        assert_eq!(shown_names(&handle_list(&data, &command).unwrap()), ["a", "c", "b", "d"]);
+    }
+
+    #[test]
+    fn reverse_without_sort_flips_the_list() {
+        let data = data_with(&["a", "b", "c"]);
+        let command = ListCommand { reverse: true, ..ListCommand::default() };
+        assert_eq!(shown_names(&handle_list(&data, &command).unwrap()), ["c", "b", "a"]);
+    }
+
+    #[test]
+    fn sorting_leaves_the_saved_order_alone() {
+        let data = data_with(&["b", "a"]);
+        let command = ListCommand { sort: Some(SortKey::Name), ..ListCommand::default() };
+        handle_list(&data, &command).unwrap();
+        assert_eq!(names(&data), ["b", "a"]);
+    }
+
+    #[test]
+    fn done_all_on_an_empty_list_does_nothing() {
+        for done in [true, false] {
+            let mut data = SaveData::new();
+            let command = DoneCommand { tasks: Vec::new(), all: true, matching: None, yes: false };
+            let outcome = handle_done_undone(&mut data, &command, done, None);
+            assert!(matches!(outcome, Ok(CommandOutcome::Nothing)));
+            assert!(data.get_tasks().is_empty());
+        }
+    }
+
+    #[test]
+    fn done_all_rejects_task_names() {
+        let mut data = data_with(&["a"]);
+        let command = DoneCommand { tasks: queries(&["a"]), all: true, matching: None, yes: false };
+        let outcome = handle_done_undone(&mut data, &command, true, None);
+        assert!(matches!(outcome, Err(CommandError::ConflictingArguments(..))));
+    }
+
+    fn remove_done_command(tasks: &[&str]) -> RemoveCommand {
+        return RemoveCommand { tasks: queries(tasks), done: true, matching: None, yes: false }
+    }
+
+    #[test]
+    fn remove_done_rejects_a_pending_task() {
+        let mut data = data_with(&["a", "b"]);
+        data.mark_task_done(0);
+        let outcome = handle_remove(&mut data, &remove_done_command(&["a", "b"]), false, None);
+        assert!(matches!(outcome, Err(CommandError::TaskNotDone(name)) if name == "b"));
+        assert_eq!(names(&data), ["a", "b"]);
+    }
+
+    #[test]
+    fn remove_done_without_names_removes_every_done_task() {
+        let mut data = data_with(&["a", "b", "c"]);
+        data.mark_task_done(0);
+        data.mark_task_done(2);
+        handle_remove(&mut data, &remove_done_command(&[]), false, None).unwrap();
+        assert_eq!(names(&data), ["b"]);
+    }
+
+    #[test]
+    fn add_stops_at_the_task_limit() {
+        let almost_full: Vec<Task> = (1..DEFAULT_MAX_TASKS).map(|number| Task::new(number.to_string())).collect();
+        let mut data = SaveData::from_tasks(almost_full);
+
+        // Duplicates and names already in the list don't count towards the limit.
+        handle_add(&mut data, &add_command(&["last", "last", "1"])).unwrap();
+        assert_eq!(data.get_tasks().len(), DEFAULT_MAX_TASKS);
+
+        let result = handle_add(&mut data, &add_command(&["one too many"]));
+        assert!(matches!(result, Err(CommandError::TaskLimitReached { max: DEFAULT_MAX_TASKS })));
+        assert_eq!(data.get_tasks().len(), DEFAULT_MAX_TASKS);
+
+        let forced = AddCommand { force: true, ..add_command(&["one too many"]) };
+        handle_add(&mut data, &forced).unwrap();
+        assert_eq!(data.get_tasks().len(), DEFAULT_MAX_TASKS + 1);
+    }
+
+    #[test]
+    fn list_filters_pending_and_done_tasks() {
+        let mut data = data_with(&["a", "b", "c"]);
+        data.mark_task_done(1);
+
+        let pending = ListCommand { pending: true, ..ListCommand::default() };
+        assert_eq!(shown_names(&handle_list(&data, &pending).unwrap()), ["a", "c"]);
+        let done = ListCommand { done: true, ..ListCommand::default() };
+        let page = handle_list(&data, &done).unwrap();
+        assert_eq!(shown_names(&page), ["b"]);
+        assert_eq!((page.matching, page.total), (1, 3));
+    }
+
+    #[test]
+    fn bump_reopens_tasks_at_the_top_in_the_given_order() {
+        let mut data = data_with(&["a", "b", "c", "d"]);
+        data.mark_task_done(2);
+        data.mark_task_done(3);
+
+        handle_bump(&mut data, &TasksCommand { tasks: queries(&["d", "c"]) }).unwrap();
+        assert_eq!(names(&data), ["d", "c", "a", "b"]);
+        assert!(data.iter().all(|task| !task.done && task.completed_at.is_none()));
+    }
+
+    #[test]
+    fn bump_rejects_unknown_tasks_without_changes() {
+        let mut data = data_with(&["a", "b"]);
+        data.mark_task_done(1);
+        let outcome = handle_bump(&mut data, &TasksCommand { tasks: queries(&["b", "missing"]) });
+        assert!(matches!(outcome, Err(CommandError::TaskNotFound(name)) if name == "missing"));
+        assert_eq!(names(&data), ["a", "b"]);
+        assert!(data.get_tasks()[1].done);
+    }
+
+    fn local_noon(year: i32, month: u32, day: u32) -> chrono::DateTime<Utc> {
+        return chrono::TimeZone::with_ymd_and_hms(&Local, year, month, day, 12, 0, 0).unwrap().with_timezone(&Utc)
+    }
+
+    #[test]
+    fn since_and_until_include_both_days() {
+        let mut data = data_with(&["april 30", "may 1", "may 2", "may 3", "no date"]);
+        for (task, day) in data.tasks_mut().iter_mut().zip([(4, 30), (5, 1), (5, 2), (5, 3)]) {
+            task.done = true;
+            task.completed_at = Some(local_noon(2024, day.0, day.1));
+        }
+        data.tasks_mut()[4].done = true;
+
+        let command = ListCommand {
+            since: Some("2024-05-01".to_string()),
+            until: Some("2024-05-02".to_string()),
+            ..ListCommand::default()
+        };
+        assert_eq!(shown_names(&handle_list(&data, &command).unwrap()), ["may 1", "may 2"]);
+    }
+
+    #[test]
+    fn since_can_filter_by_creation_date() {
+        let mut data = data_with(&["old", "new"]);
+        data.tasks_mut()[0].created_at = Some(local_noon(2024, 4, 30));
+        data.tasks_mut()[1].created_at = Some(local_noon(2024, 5, 1));
+
+        let command = ListCommand { since: Some("2024-05-01".to_string()), by: DateField::Created, ..ListCommand::default() };
+        assert_eq!(shown_names(&handle_list(&data, &command).unwrap()), ["new"]);
+    }
+
+    #[test]
+    fn since_rejects_invalid_dates() {
+        let data = data_with(&["a"]);
+        let command = ListCommand { since: Some("May 1st".to_string()), ..ListCommand::default() };
+        assert!(matches!(handle_list(&data, &command), Err(CommandError::InvalidDate(date)) if date == "May 1st"));
+    }
+
+    #[test]
+    fn clear_before_only_removes_older_done_tasks() {
+        let mut data = data_with(&["old", "on the day", "new", "no date", "pending"]);
+        for (task, day) in data.tasks_mut().iter_mut().zip([(4, 30), (5, 1), (5, 2)]) {
+            task.done = true;
+            task.completed_at = Some(local_noon(2024, day.0, day.1));
+        }
+        // Done without a completion time, like tasks from older versions.
+        data.tasks_mut()[3].done = true;
+
+        let command = ClearCommand { yes: true, done: true, before: Some("2024-05-01".to_string()) };
+        handle_clear(&mut data, &command, false, |_| panic!("--yes must not prompt")).unwrap();
+        assert_eq!(names(&data), ["on the day", "new", "no date", "pending"]);
+    }
+
+    #[test]
+    fn fuzzy_names_resolve_only_when_confirmed() {
+        let data = data_with(&["buy milk", "walk the dog"]);
+        let asked = std::cell::RefCell::new(Vec::new());
+        let yes = |prompt: &str| { asked.borrow_mut().push(prompt.to_string()); true };
+        let no = |_: &str| false;
+
+        assert_eq!(resolve_tasks(data.get_tasks(), &queries(&["by milk"]), Some(&yes)).unwrap(), [0]);
+        assert_eq!(*asked.borrow(), ["Did you mean 'buy milk'?"]);
+        let declined = resolve_tasks(data.get_tasks(), &queries(&["by milk"]), Some(&no));
+        assert!(matches!(declined, Err(CommandError::TaskNotFound(query)) if query == "by milk"));
+    }
+
+    #[test]
+    fn fuzzy_only_asks_about_unknown_names_with_a_close_match() {
+        let data = data_with(&["buy milk"]);
+        let never = |_: &str| panic!("must not ask");
+        assert_eq!(resolve_tasks(data.get_tasks(), &queries(&["buy milk", "1"]), Some(&never)).unwrap(), [0]);
+        let unrelated = resolve_tasks(data.get_tasks(), &queries(&["taxes"]), Some(&never));
+        assert!(matches!(unrelated, Err(CommandError::TaskNotFound(_))));
+    }
+
+    fn swap(data: &mut SaveData, first: &str, second: &str) -> Result<CommandOutcome, CommandError> {
+        return handle_swap(data, &SwapCommand { first: first.to_string(), second: second.to_string() })
+    }
+
+    #[test]
+    fn swap_exchanges_adjacent_and_distant_tasks() {
+        let mut data = data_with(&["a", "b", "c", "d"]);
+        swap(&mut data, "a", "b").unwrap();
+        assert_eq!(names(&data), ["b", "a", "c", "d"]);
+        swap(&mut data, "d", "b").unwrap();
+        assert_eq!(names(&data), ["d", "a", "c", "b"]);
+    }
+
+    #[test]
+    fn swap_rejects_the_same_task_twice() {
+        let mut data = data_with(&["a", "b"]);
+        assert!(matches!(swap(&mut data, "a", "a"), Err(CommandError::SameTask(name)) if name == "a"));
+        assert_eq!(names(&data), ["a", "b"]);
+    }
+
+    #[test]
+    fn swap_rejects_missing_tasks() {
+        let mut data = data_with(&["a", "b"]);
+        assert!(matches!(swap(&mut data, "a", "missing"), Err(CommandError::TaskNotFound(name)) if name == "missing"));
+        assert!(matches!(swap(&mut data, "missing", "b"), Err(CommandError::TaskNotFound(name)) if name == "missing"));
+        assert_eq!(names(&data), ["a", "b"]);
+    }
+
+    fn blocked_data() -> SaveData {
+        let mut data = data_with(&["a", "b"]);
+        data.tasks_mut()[1].blocked_by = vec!["a".to_string()];
+        return data
+    }
+
+    fn mark(data: &mut SaveData, tasks: &[&str], done: bool) -> Result<CommandOutcome, CommandError> {
+        let command = DoneCommand { tasks: queries(tasks), all: false, matching: None, yes: false };
+        return handle_done_undone(data, &command, done, None)
+    }
+
+    #[test]
+    fn blocked_tasks_cannot_be_marked_done() {
+        let mut data = blocked_data();
+        assert!(matches!(mark(&mut data, &["b"], true), Err(CommandError::Blocked { name, by }) if name == "b" && by == "a"));
+        assert!(data.iter().all(|task| !task.done));
+    }
+
+    #[test]
+    fn finishing_the_blocker_unblocks() {
+        let mut data = blocked_data();
+        mark(&mut data, &["a"], true).unwrap();
+        mark(&mut data, &["b"], true).unwrap();
+        assert!(data.iter().all(|task| task.done));
+
+        // Reopening the blocker blocks again.
+        mark(&mut data, &["a", "b"], false).unwrap();
+        mark(&mut data, &["a"], true).unwrap();
+        mark(&mut data, &["a"], false).unwrap();
+        assert!(matches!(mark(&mut data, &["b"], true), Err(CommandError::Blocked { .. })));
+    }
+
+    #[test]
+    fn a_blocker_marked_done_alongside_does_not_block() {
+        let mut data = blocked_data();
+        mark(&mut data, &["b", "a"], true).unwrap();
+        assert!(data.iter().all(|task| task.done));
+    }
+
+    #[test]
+    fn removed_blockers_no_longer_block() {
+        let mut data = blocked_data();
+        data.remove_task(0);
+        mark(&mut data, &["b"], true).unwrap();
+    }
+
+    #[test]
+    fn edit_checks_blockers() {
+        let mut data = blocked_data();
+        let result = apply_edit(&mut data, "[ ] a\n[x] b\n");
+        assert!(matches!(result, Err(CommandError::Blocked { name, by }) if name == "b" && by == "a"));
+        assert!(data.iter().all(|task| !task.done));
+
+        let outcome = apply_edit(&mut data, "[x] a\n[x] b\n").unwrap();
+        assert!(matches!(outcome, CommandOutcome::Edited { added: 0, removed: 0, toggled: 2 }));
+        assert!(data.iter().all(|task| task.done));
+    }
+
+    #[test]
+    fn edit_keeps_blocked_tasks_that_were_already_done() {
+        let mut data = blocked_data();
+        data.mark_task_done(1);
+        apply_edit(&mut data, "[ ] a\n[x] b\n[ ] c\n").unwrap();
+        assert_eq!(names(&data), ["a", "b", "c"]);
+    }
+
+    #[test]
+    fn copy_defaults_to_a_copy_suffix_and_resets_done() {
+        let mut data = data_with(&["a"]);
+        data.tasks_mut()[0].category = Some("work".to_string());
+        data.mark_task_done(0);
+
+        handle_copy(&mut data, &CopyCommand { task: "a".to_string(), new_name: None }).unwrap();
+        assert_eq!(names(&data), ["a", "a (copy)"]);
+        let copy = &data.get_tasks()[1];
+        assert!(!copy.done && copy.completed_at.is_none());
+        assert_eq!(copy.category.as_deref(), Some("work"));
+        assert!(data.get_tasks()[0].done);
+    }
+
+    #[test]
+    fn copy_uses_the_given_name() {
+        let mut data = data_with(&["a"]);
+        handle_copy(&mut data, &CopyCommand { task: "a".to_string(), new_name: Some(" b ".to_string()) }).unwrap();
+        assert_eq!(names(&data), ["a", "b"]);
+    }
+
+    #[test]
+    fn copy_rejects_taken_names_and_missing_tasks() {
+        let mut data = data_with(&["a", "a (copy)"]);
+        let taken = handle_copy(&mut data, &CopyCommand { task: "a".to_string(), new_name: None });
+        assert!(matches!(taken, Err(CommandError::TaskExists(name)) if name == "a (copy)"));
+        let missing = handle_copy(&mut data, &CopyCommand { task: "b".to_string(), new_name: None });
+        assert!(matches!(missing, Err(CommandError::TaskNotFound(name)) if name == "b"));
+        assert_eq!(names(&data), ["a", "a (copy)"]);
+    }
+
+    #[test]
+    fn prepended_tasks_keep_their_order() {
+        let mut data = data_with(&["a"]);
+        handle_add(&mut data, &AddCommand { prepend: true, append: false, ..add_command(&["b", "c"]) }).unwrap();
+        assert_eq!(names(&data), ["b", "c", "a"]);
+
+        handle_add(&mut data, &add_command(&["d"])).unwrap();
+        assert_eq!(names(&data), ["b", "c", "a", "d"]);
+    }
+
+    #[test]
+    fn add_position_defaults_from_the_environment() {
+        let _lock = crate::ENV_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let previous = env::var_os("TODO_ADD_POSITION");
+        let unset = AddCommand { append: false, ..add_command(&["b"]) };
+
+        env::set_var("TODO_ADD_POSITION", "top");
+        let mut data = data_with(&["a"]);
+        handle_add(&mut data, &unset).unwrap();
+        assert_eq!(names(&data), ["b", "a"]);
+        let mut data = data_with(&["a"]);
+        handle_add(&mut data, &add_command(&["b"])).unwrap();
+        assert_eq!(names(&data), ["a", "b"]);
+
+        env::remove_var("TODO_ADD_POSITION");
+        let mut data = data_with(&["a"]);
+        handle_add(&mut data, &unset).unwrap();
+        assert_eq!(names(&data), ["a", "b"]);
+
+        if let Some(previous) = previous {
+            env::set_var("TODO_ADD_POSITION", previous);
+        }
+    }
+
+    fn note_command(task: &str, text: &[&str]) -> NoteCommand {
+        return NoteCommand { task: task.to_string(), text: queries(text) }
+    }
+
+    #[test]
+    fn notes_are_appended_in_order() {
+        let mut data = data_with(&["a"]);
+        handle_note(&mut data, &note_command("a", &["called", "the", "shop"])).unwrap();
+        handle_note(&mut data, &note_command("a", &[" closed today "])).unwrap();
+
+        let notes: Vec<&str> = data.get_tasks()[0].notes.iter().map(|note| note.text.as_str()).collect();
+        assert_eq!(notes, ["called the shop", "closed today"]);
+        assert!(data.get_tasks()[0].notes[0].added_at <= data.get_tasks()[0].notes[1].added_at);
+    }
+
+    #[test]
+    fn empty_notes_and_missing_tasks_are_rejected() {
+        let mut data = data_with(&["a"]);
+        assert!(matches!(handle_note(&mut data, &note_command("a", &[" ", ""])), Err(CommandError::EmptyNote)));
+        assert!(matches!(handle_note(&mut data, &note_command("b", &["hi"])), Err(CommandError::TaskNotFound(name)) if name == "b"));
+        assert!(data.get_tasks()[0].notes.is_empty());
+    }
+
+    fn next_name(data: &SaveData) -> Option<String> {
+        return match handle_next(data, &NextCommand { sort: None }).unwrap() {
+            CommandOutcome::Next(next) => next,
+            _ => panic!("expected a next task")
+        }
+    }
+
+    #[test]
+    fn next_is_none_when_nothing_is_pending() {
+        assert_eq!(next_name(&data_with(&[])), None);
+
+        let mut data = data_with(&["a", "b"]);
+        data.mark_task_done(0);
+        data.mark_task_done(1);
+        assert_eq!(next_name(&data), None);
+    }
+
+    #[test]
+    fn next_prefers_pins_and_skips_done_and_blocked_tasks() {
+        let mut data = data_with(&["a", "b", "c", "d"]);
+        data.mark_task_done(0);
+        assert_eq!(next_name(&data), Some("b".to_string()));
+
+        data.tasks_mut()[3].pinned = true;
+        data.tasks_mut()[3].blocked_by = vec!["c".to_string()];
+        assert_eq!(next_name(&data), Some("b".to_string()));
+
+        data.tasks_mut()[2].pinned = true;
+        assert_eq!(next_name(&data), Some("c".to_string()));
+    }
+
+    fn regex_rename(data: &mut SaveData, from: &str, to: &str) -> CommandOutcome {
+        let command = RenameCommand { from: from.to_string(), to: to.to_string(), regex: true, yes: true };
+        return handle_rename(data, &command, false).unwrap()
+    }
+
+    #[test]
+    fn regex_renames_rewrite_blockers_from_the_old_names() {
+        let mut data = data_with(&["abb", "ab", "c"]);
+        data.tasks_mut()[2].blocked_by = vec!["abb".to_string()];
+        regex_rename(&mut data, "b$", "");
+        assert_eq!(names(&data), ["ab", "a", "c"]);
+        assert_eq!(data.get_tasks()[2].blocked_by, ["ab"]);
+    }
+
+    #[test]
+    fn regex_renames_can_strip_a_prefix() {
+        let mut data = data_with(&["draft: post", "draft: mail", "call"]);
+        data.tasks_mut()[2].blocked_by = vec!["draft: mail".to_string()];
+        regex_rename(&mut data, "^draft: ", "");
+        assert_eq!(names(&data), ["post", "mail", "call"]);
+        assert_eq!(data.get_tasks()[2].blocked_by, ["mail"]);
+    }
+
+    #[test]
+    fn colliding_regex_renames_are_skipped() {
+        let mut data = data_with(&["a1", "a2", "b", "c1"]);
+        let outcome = regex_rename(&mut data, r"\d$", "");
+        let CommandOutcome::Renames { renames, conflicts, applied } = outcome else { panic!("expected renames") };
+        assert!(applied);
+        assert_eq!(renames, [("c1".to_string(), "c".to_string())]);
+        assert_eq!(conflicts, [("a1".to_string(), "a".to_string()), ("a2".to_string(), "a".to_string())]);
+        assert_eq!(names(&data), ["a1", "a2", "b", "c"]);
+
+        let outcome = regex_rename(&mut data, "^a1$", "b");
+        let CommandOutcome::Renames { renames, conflicts, .. } = outcome else { panic!("expected renames") };
+        assert!(renames.is_empty());
+        assert_eq!(conflicts, [("a1".to_string(), "b".to_string())]);
+        assert_eq!(names(&data), ["a1", "a2", "b", "c"]);
+    }
+
+    #[test]
+    fn add_done_logs_finished_tasks() {
+        let mut data = data_with(&["a"]);
+        handle_add(&mut data, &AddCommand { done: true, ..add_command(&["b"]) }).unwrap();
+        let added = &data.get_tasks()[1];
+        assert_eq!(added.name, "b");
+        assert!(added.done && added.completed_at.is_some());
+        assert!(!data.get_tasks()[0].done);
+    }
+}
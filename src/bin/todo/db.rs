@@ -1,15 +1,106 @@
-use std::{fs::{self, File}, path::PathBuf};
+use std::{collections::HashMap, env, fs::{self, File}, io::{self, Read, Write}, ops::{Deref, DerefMut}, path::PathBuf};
 
+use chrono::{DateTime, TimeDelta, Utc};
 use serde::{Deserialize, Serialize};
-use serde_json::{to_writer, from_reader};
+use serde_json::{to_writer, to_writer_pretty, from_reader, from_value, Value};
 use platform_dirs::AppDirs;
 
-#[derive(Serialize, Deserialize)]
+/// The key names are todos.json's format, so each field pins its key explicitly.
+/// Renaming a Rust field must not rename the key, or existing files stop loading.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
 pub struct Task {
+    #[serde(rename = "name")]
     pub name: String,
-    pub done: bool
+    #[serde(rename = "done")]
+    pub done: bool,
+    /// When the task was last marked done. Cleared when it is marked undone.
+    #[serde(rename = "completed_at", default, skip_serializing_if = "Option::is_none")]
+    pub completed_at: Option<DateTime<Utc>>,
+    /// When the task was added. Missing for tasks added by older versions.
+    #[serde(rename = "created_at", default, skip_serializing_if = "Option::is_none")]
+    pub created_at: Option<DateTime<Utc>>,
+    /// Picks the color the task is listed in, see `TODO_CATEGORY_COLORS`.
+    #[serde(rename = "category", default, skip_serializing_if = "Option::is_none")]
+    pub category: Option<String>,
+    /// Names of tasks that must be done before this one can be.
+    #[serde(rename = "blocked_by", default, skip_serializing_if = "Vec::is_empty")]
+    pub blocked_by: Vec<String>,
+    /// Pinned tasks are always listed first, whatever the sort.
+    #[serde(rename = "pinned", default, skip_serializing_if = "std::ops::Not::not")]
+    pub pinned: bool,
+    /// A running log of notes, oldest first.
+    #[serde(rename = "notes", default, skip_serializing_if = "Vec::is_empty")]
+    pub notes: Vec<NoteEntry>
+}
+
+/// One entry in a task's notes, added by `todo note`.
+#[derive(Serialize, Deserialize, Clone, Debug, PartialEq, Eq, Hash)]
+#[serde(rename_all = "snake_case")]
+pub struct NoteEntry {
+    #[serde(rename = "text")]
+    pub text: String,
+    #[serde(rename = "added_at")]
+    pub added_at: DateTime<Utc>
+}
+
+impl Task {
+    /// Creates a pending task with the given name, created now. Two tasks made this way
+    /// usually differ in `created_at`, so they only compare equal once that matches too.
+    pub fn new(name: impl Into<String>) -> Task {
+        return Task {name: name.into(), done: false, completed_at: None, created_at: Some(Utc::now()), category: None, blocked_by: Vec::new(), pinned: false, notes: Vec::new()}
+    }
+
+    /// Sets the done state, recording the completion time when a pending task is marked done.
+    pub fn set_done(&mut self, done: bool) {
+        if done && !self.done {
+            self.completed_at = Some(Utc::now());
+        } else if !done {
+            self.completed_at = None;
+        }
+        self.done = done;
+    }
+
+    /// How long the task took from being added to being marked done.
+    /// `None` if it isn't done, either time is missing, or the times are out of order
+    /// (e.g. from a clock change or an older migration).
+    pub fn time_to_complete(&self) -> Option<TimeDelta> {
+        let took = self.completed_at? - self.created_at?;
+        return if took < TimeDelta::zero() { None } else { Some(took) }
+    }
+}
+
+/// A done task that was moved out of the live list by `todo archive`.
+#[derive(Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub struct ArchivedTask {
+    #[serde(flatten)]
+    pub task: Task,
+    #[serde(rename = "archived_at")]
+    pub archived_at: DateTime<Utc>
+}
+
+/// The current layout of todos.json. Bump it and migrate in `load_tasks` when the layout changes.
+const FORMAT_VERSION: u32 = 1;
+
+/// todos.json as it is read. Files from before the envelope are a bare task array.
+/// The tasks are parsed only once the version is known to be readable, since a
+/// newer version may have changed their fields.
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SaveFile {
+    Versioned { version: u32, tasks: Value },
+    Legacy(Vec<Value>)
+}
+
+/// todos.json as it is written.
+#[derive(Serialize)]
+struct SaveFileRef<'a> {
+    version: u32,
+    tasks: &'a [Task]
 }
 
+#[derive(Default)]
 pub(crate) struct SaveData {
     tasks: Vec<Task>
 }
@@ -18,81 +109,542 @@ pub(crate) fn exactly_matches(task: &Task, query_string: &String) -> bool {
     return task.name == *query_string;
 }
 
-pub(crate) fn get_index(tasks: &Vec<Task>, query_string: &String) -> Option<usize> {
+pub(crate) fn get_index(tasks: &[Task], query_string: &String) -> Option<usize> {
     return tasks.iter().position(|r| exactly_matches(r, query_string))
 }
 
+/// Returns the first of `task`'s blockers that is in `tasks` and not done.
+/// Blockers that were removed from the list no longer block.
+pub(crate) fn pending_blocker<'a>(tasks: &[Task], task: &'a Task) -> Option<&'a String> {
+    return task.blocked_by.iter()
+        .find(|blocker| get_index(tasks, blocker).is_some_and(|index| !tasks[index].done))
+}
+
 impl SaveData {
     pub fn new() -> SaveData {
-        return SaveData {tasks: Vec::new()}
+        return SaveData::default()
+    }
+
+    /// Starts from a known list instead of loading one.
+    pub fn from_tasks(tasks: Vec<Task>) -> SaveData {
+        return SaveData {tasks}
+    }
+
+    /// Returns the app directories, preferring `XDG_DATA_HOME` and `XDG_CONFIG_HOME`
+    /// when they are set to absolute paths on Linux and other XDG platforms.
+    ///
+    /// Fails when there is no home directory (e.g. in containers or cron jobs),
+    /// unless `XDG_DATA_HOME` says where to save instead.
+    pub(crate) fn get_app_dirs() -> io::Result<AppDirs> {
+        return SaveData::app_dirs_from(AppDirs::new(Some("todo"), true),
+            SaveData::xdg_dir("XDG_DATA_HOME"), SaveData::xdg_dir("XDG_CONFIG_HOME"))
+    }
+
+    /// Picks the folders from the platform defaults and the XDG overrides, kept apart
+    /// from the lookups so the no-home fallback can be tested.
+    fn app_dirs_from(home: Option<AppDirs>, data_home: Option<PathBuf>, config_home: Option<PathBuf>) -> io::Result<AppDirs> {
+        let mut app_dirs = match (home, &data_home) {
+            (Some(app_dirs), _) => app_dirs,
+            // Only the data and config folders are used, the config one is replaced below if set.
+            (None, Some(data_home)) => {
+                let dir = data_home.join("todo");
+                AppDirs {cache_dir: dir.clone(), config_dir: dir.clone(), data_dir: dir.clone(), state_dir: dir}
+            }
+            (None, None) if cfg!(all(unix, not(target_os = "macos"))) => return Err(io::Error::new(io::ErrorKind::NotFound,
+                "no home directory found, set XDG_DATA_HOME to choose where tasks are saved")),
+            (None, None) => return Err(io::Error::new(io::ErrorKind::NotFound, "no home directory found"))
+        };
+        if let Some(data_home) = data_home {
+            app_dirs.data_dir = data_home.join("todo");
+        }
+        if let Some(config_home) = config_home {
+            app_dirs.config_dir = config_home.join("todo");
+        }
+        return Ok(app_dirs);
+    }
+
+    /// Reads an XDG base directory variable. The spec says relative paths must be ignored.
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn xdg_dir(name: &str) -> Option<PathBuf> {
+        let path = PathBuf::from(env::var_os(name)?);
+        return if path.is_absolute() { Some(path) } else { None };
+    }
+
+    /// Windows and macOS keep their own folders. Shells like Git Bash or shared dotfiles
+    /// can set the XDG variables there, and following them would hide the existing list.
+    #[cfg(not(all(unix, not(target_os = "macos"))))]
+    fn xdg_dir(_name: &str) -> Option<PathBuf> {
+        return None;
     }
 
-    pub(crate) fn get_data_paths() -> (AppDirs, PathBuf) {
-        let app_dirs = AppDirs::new(Some("todo"), true).unwrap();
+    pub(crate) fn get_data_paths() -> io::Result<(AppDirs, PathBuf)> {
+        let app_dirs = SaveData::get_app_dirs()?;
         let data_file_path = app_dirs.data_dir.join("todos.json");
-        return (app_dirs, data_file_path);
+        return Ok((app_dirs, data_file_path));
     }
 
-    pub fn show_data_file_path(&self) {
-        let data_file_path = SaveData::get_data_paths().1;
-        println!("Data file: {}", data_file_path.as_path().to_str().unwrap());
+    pub(crate) fn get_archive_paths() -> io::Result<(AppDirs, PathBuf)> {
+        let app_dirs = SaveData::get_app_dirs()?;
+        let archive_file_path = app_dirs.data_dir.join("archive.json");
+        return Ok((app_dirs, archive_file_path));
     }
 
-    pub fn load_tasks(&mut self) -> Result<(), serde_json::Error> {
-        let data_paths = SaveData::get_data_paths();
-        let app_dirs = data_paths.0;
-        let data_file_path = data_paths.1;
+    pub fn load_tasks(&mut self) -> Result<(), io::Error> {
+        let (app_dirs, data_file_path) = SaveData::get_data_paths()?;
 
-        fs::create_dir_all(&app_dirs.data_dir).unwrap();
+        fs::create_dir_all(&app_dirs.data_dir)?;
 
         if !data_file_path.exists() {return Ok(())}
 
-        let file = File::open(data_file_path).unwrap();
+        let file = File::open(data_file_path)?;
+        self.tasks = SaveData::read_tasks(file)?;
+
+        return Ok(())
+    }
+
+    /// Parses tasks saved by `save_tasks` or `export_json`.
+    /// Legacy bare-array files are migrated simply by reading them, the next save writes the envelope.
+    pub fn read_tasks(reader: impl Read) -> Result<Vec<Task>, io::Error> {
+        match from_reader(reader)? {
+            SaveFile::Versioned { version, .. } if version > FORMAT_VERSION => {
+                return Err(io::Error::new(io::ErrorKind::InvalidData, format!(
+                    "the file uses format version {}, this version of todo only reads up to {}",
+                    version, FORMAT_VERSION)))
+            }
+            SaveFile::Versioned { tasks, .. } => return Ok(from_value(tasks)?),
+            SaveFile::Legacy(tasks) => return Ok(from_value(Value::Array(tasks))?)
+        }
+    }
+
+    /// Whether todos.json uses the bare-array layout from before the versioned envelope.
+    pub fn data_file_is_legacy() -> Result<bool, io::Error> {
+        let data_file_path = SaveData::get_data_paths()?.1;
+        if !data_file_path.exists() {return Ok(false)}
 
-        let result: Vec<Task> = from_reader(file)?;
-        self.tasks = result;
+        let file = File::open(data_file_path)?;
+        return Ok(matches!(from_reader(file)?, SaveFile::Legacy(_)))
+    }
+
+    /// Copies todos.json to todos.json.bak, returning the backup's path.
+    pub fn backup_data_file() -> Result<PathBuf, io::Error> {
+        let data_file_path = SaveData::get_data_paths()?.1;
+        let backup_path = data_file_path.with_extension("json.bak");
+        fs::copy(data_file_path, &backup_path)?;
+        return Ok(backup_path)
+    }
 
+    /// Writes every task with all its fields as indented JSON, for `todo export`.
+    /// It uses the same envelope as todos.json, so `read_tasks` can read it back.
+    pub fn export_json(&self, writer: impl Write) -> Result<(), io::Error> {
+        to_writer_pretty(writer, &SaveFileRef {version: FORMAT_VERSION, tasks: &self.tasks})?;
         return Ok(())
     }
 
-    pub fn save_tasks(&self) -> Result<(), serde_json::Error> {
-        let app_dirs = AppDirs::new(Some("todo"), true).unwrap();
-        let data_file_path = app_dirs.data_dir.join("todos.json");
+    /// Writes the tasks in todos.json's compact envelope format.
+    fn write_tasks(&self, writer: impl Write) -> Result<(), io::Error> {
+        to_writer(writer, &SaveFileRef {version: FORMAT_VERSION, tasks: &self.tasks})?;
+        return Ok(())
+    }
+
+    pub fn save_tasks(&self) -> Result<(), io::Error> {
+        let (app_dirs, data_file_path) = SaveData::get_data_paths()?;
 
-        fs::create_dir_all(&app_dirs.data_dir).unwrap();
+        fs::create_dir_all(&app_dirs.data_dir)?;
 
-        let file = File::create(data_file_path).unwrap();
+        // Write to a temporary file first so readers never see a half-written list.
+        let temp_file_path = data_file_path.with_extension("json.tmp");
+        let file = File::create(&temp_file_path)?;
 
-        to_writer(file, &self.tasks)?;
+        self.write_tasks(file)?;
+        fs::rename(temp_file_path, data_file_path)?;
 
         return Ok(())
     }
 
-    pub fn get_tasks(&self) -> &Vec<Task> {
+    pub fn get_tasks(&self) -> &[Task] {
         return &self.tasks;
     }
 
+    pub fn tasks_mut(&mut self) -> &mut Vec<Task> {
+        return &mut self.tasks;
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, Task> {
+        return self.tasks.iter();
+    }
+
+    /// Takes the tasks out, e.g. to keep the old list after replacing it.
+    pub fn into_tasks(self) -> Vec<Task> {
+        return self.tasks;
+    }
+
     pub fn add_task(&mut self, task: Task) {
         self.tasks.push(task)
     }
 
+    /// Inserts a task at `index`, shifting the tasks after it down.
+    pub fn add_task_at(&mut self, task: Task, index: usize) {
+        self.tasks.insert(index, task)
+    }
+
     pub fn remove_task(&mut self, task_index: usize) {
         self.tasks.remove(task_index);
     }
 
+    /// Moves a task to `new_index`, shifting the tasks in between.
+    pub fn move_task(&mut self, task_index: usize, new_index: usize) {
+        let task = self.tasks.remove(task_index);
+        self.tasks.insert(new_index, task);
+    }
+
+    /// Renames a task, updating other tasks that are blocked by it.
+    pub fn rename_task(&mut self, task_index: usize, new_name: String) {
+        self.rename_tasks(vec![(task_index, new_name)]);
+    }
+
+    /// Renames several tasks at once. Blockers are rewritten from the old names in one
+    /// pass, so a rename that produces another renamed task's old name isn't followed again.
+    pub fn rename_tasks(&mut self, renames: Vec<(usize, String)>) {
+        let mut new_names = HashMap::new();
+        for (task_index, new_name) in renames {
+            let old_name = std::mem::replace(&mut self.tasks[task_index].name, new_name.clone());
+            new_names.insert(old_name, new_name);
+        }
+        for task in &mut self.tasks {
+            for blocker in &mut task.blocked_by {
+                if let Some(new_name) = new_names.get(blocker) {
+                    *blocker = new_name.clone();
+                }
+            }
+        }
+    }
+
+    /// Exchanges the positions of two tasks.
+    pub fn swap_tasks(&mut self, first_index: usize, second_index: usize) {
+        self.tasks.swap(first_index, second_index);
+    }
+
     pub fn mark_task_done(&mut self, task_index: usize) -> bool {
         let was_done = self.tasks[task_index].done;
-        self.tasks[task_index].done = true;
+        self.tasks[task_index].set_done(true);
         return was_done
     }
 
     pub fn mark_task_undone(&mut self, task_index: usize) -> bool {
         let was_undone = !self.tasks[task_index].done;
-        self.tasks[task_index].done = false;
+        self.tasks[task_index].set_done(false);
         return was_undone
     }
 
     pub fn clear_tasks(&mut self) {
         self.tasks = Vec::new();
     }
+
+    /// Keeps only the tasks for which `keep` returns true.
+    pub fn retain_tasks(&mut self, keep: impl FnMut(&Task) -> bool) {
+        self.tasks.retain(keep);
+    }
+
+    pub fn load_archive() -> Result<Vec<ArchivedTask>, io::Error> {
+        let archive_file_path = SaveData::get_archive_paths()?.1;
+
+        if !archive_file_path.exists() {return Ok(Vec::new())}
+
+        let file = File::open(archive_file_path)?;
+
+        return Ok(from_reader(file)?)
+    }
+
+    /// Moves all done tasks to the archive file, returning how many were moved.
+    ///
+    /// The archive is written to a temporary file and renamed into place, so it
+    /// is never left half-written. The live list is only changed once that
+    /// succeeds.
+    pub fn archive_done(&mut self) -> Result<usize, io::Error> {
+        let (app_dirs, archive_file_path) = SaveData::get_archive_paths()?;
+        let mut archive = SaveData::load_archive()?;
+
+        let now = Utc::now();
+        let previous_len = archive.len();
+        archive.extend(self.tasks.iter()
+            .filter(|task| task.done)
+            .map(|task| ArchivedTask {task: task.clone(), archived_at: now}));
+        let archived_count = archive.len() - previous_len;
+        if archived_count == 0 {return Ok(0)}
+
+        fs::create_dir_all(&app_dirs.data_dir)?;
+
+        let temp_file_path = archive_file_path.with_extension("json.tmp");
+        let file = File::create(&temp_file_path)?;
+        to_writer(file, &archive)?;
+        fs::rename(temp_file_path, archive_file_path)?;
+
+        self.tasks.retain(|task| !task.done);
+        return Ok(archived_count)
+    }
+}
+
+/// Saves the task list when dropped, so changes aren't lost if a later step
+/// panics, e.g. printing to a closed pipe. `save` saves early and reports
+/// errors, `disarm` skips saving.
+pub(crate) struct AutosaveGuard<'a> {
+    data: &'a mut SaveData,
+    armed: bool,
+    saver: Saver<'a>
+}
+
+/// How an `AutosaveGuard` saves. `SaveData::save_tasks` unless a test swaps it.
+type Saver<'a> = Box<dyn FnMut(&SaveData) -> Result<(), io::Error> + 'a>;
+
+impl<'a> AutosaveGuard<'a> {
+    /// Wraps `data`, saving it to todos.json on drop only if `armed`.
+    pub fn new(data: &'a mut SaveData, armed: bool) -> AutosaveGuard<'a> {
+        return AutosaveGuard::with_saver(data, armed, SaveData::save_tasks)
+    }
+
+    /// Like `new`, but saves by calling `saver` instead of writing todos.json.
+    pub fn with_saver(data: &'a mut SaveData, armed: bool, saver: impl FnMut(&SaveData) -> Result<(), io::Error> + 'a) -> AutosaveGuard<'a> {
+        return AutosaveGuard {data, armed, saver: Box::new(saver)}
+    }
+
+    pub fn disarm(&mut self) {
+        self.armed = false;
+    }
+
+    /// Saves now if armed, then disarms so the drop doesn't save again.
+    pub fn save(&mut self) -> Result<(), io::Error> {
+        if !self.armed {return Ok(())}
+        self.armed = false;
+        return (self.saver)(self.data)
+    }
+}
+
+impl Deref for AutosaveGuard<'_> {
+    type Target = SaveData;
+
+    fn deref(&self) -> &SaveData {
+        return self.data;
+    }
+}
+
+impl DerefMut for AutosaveGuard<'_> {
+    fn deref_mut(&mut self) -> &mut SaveData {
+        return self.data;
+    }
+}
+
+impl Drop for AutosaveGuard<'_> {
+    fn drop(&mut self) {
+        if self.save().is_err() {
+            eprintln!("Unable to save the task list.");
+        }
+    }
+}
+
+impl<'a> IntoIterator for &'a SaveData {
+    type Item = &'a Task;
+    type IntoIter = std::slice::Iter<'a, Task>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        return self.tasks.iter();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn task_key_names_are_stable() {
+        let time = DateTime::parse_from_rfc3339("2024-05-01T12:00:00Z").unwrap().with_timezone(&Utc);
+        let task = Task {
+            name: "buy milk".to_string(),
+            done: true,
+            completed_at: Some(time),
+            created_at: Some(time),
+            category: Some("home".to_string()),
+            blocked_by: vec!["go to the shop".to_string()],
+            pinned: true,
+            notes: vec![NoteEntry {text: "semi-skimmed".to_string(), added_at: time}]
+        };
+        let json = serde_json::to_value(&task).unwrap();
+
+        let keys: Vec<&str> = json.as_object().unwrap().keys().map(String::as_str).collect();
+        assert_eq!(keys, ["blocked_by", "category", "completed_at", "created_at", "done", "name", "notes", "pinned"]);
+        let note_keys: Vec<&str> = json["notes"][0].as_object().unwrap().keys().map(String::as_str).collect();
+        assert_eq!(note_keys, ["added_at", "text"]);
+    }
+
+    #[test]
+    fn from_tasks_keeps_the_tasks_in_order() {
+        let tasks = vec![Task::new("a"), Task::new("b")];
+        let data = SaveData::from_tasks(tasks.clone());
+        assert_eq!(data.get_tasks(), tasks);
+        assert_eq!(data.into_tasks(), tasks);
+    }
+
+    #[test]
+    fn legacy_files_are_saved_in_the_envelope() {
+        let legacy = r#"[{"name":"buy milk","done":true},{"name":"walk the dog","done":false}]"#;
+        let tasks = SaveData::read_tasks(legacy.as_bytes()).unwrap();
+        assert_eq!(tasks.len(), 2);
+        assert_eq!(tasks[0].name, "buy milk");
+        assert!(tasks[0].done && !tasks[1].done);
+
+        let mut out = Vec::new();
+        SaveData::from_tasks(tasks).write_tasks(&mut out).unwrap();
+        assert_eq!(String::from_utf8(out).unwrap(),
+            r#"{"version":1,"tasks":[{"name":"buy milk","done":true},{"name":"walk the dog","done":false}]}"#);
+    }
+
+    #[test]
+    fn newer_versions_are_rejected_before_reading_tasks() {
+        let future = r#"{"version":2,"tasks":[{"title":"buy milk","state":"open"}]}"#;
+        let err = SaveData::read_tasks(future.as_bytes()).unwrap_err();
+        assert_eq!(err.kind(), io::ErrorKind::InvalidData);
+        assert!(err.to_string().contains("format version 2"), "{}", err);
+    }
+
+    #[test]
+    fn completion_time_follows_done_transitions() {
+        let mut task = Task::new("a");
+        assert_eq!(task.completed_at, None);
+
+        task.set_done(true);
+        let first = task.completed_at.expect("marking done records the time");
+        task.set_done(true);
+        assert_eq!(task.completed_at, Some(first), "marking a done task done again keeps the time");
+
+        task.set_done(false);
+        assert_eq!(task.completed_at, None);
+
+        task.set_done(true);
+        assert!(task.completed_at.is_some_and(|second| second >= first));
+    }
+
+    #[test]
+    #[cfg(all(unix, not(target_os = "macos")))]
+    fn data_path_follows_xdg_data_home() {
+        let _lock = crate::ENV_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let previous = env::var_os("XDG_DATA_HOME");
+        let data_home = env::temp_dir().join(format!("todo-xdg-test-{}", std::process::id()));
+
+        env::set_var("XDG_DATA_HOME", &data_home);
+        let absolute = SaveData::get_data_paths().map(|(_, path)| path);
+        // Relative paths are ignored, as the spec says.
+        env::set_var("XDG_DATA_HOME", "relative");
+        let relative = SaveData::get_data_paths().map(|(_, path)| path);
+        match previous {
+            Some(previous) => env::set_var("XDG_DATA_HOME", previous),
+            None => env::remove_var("XDG_DATA_HOME")
+        }
+
+        assert_eq!(absolute.unwrap(), data_home.join("todo").join("todos.json"));
+        assert!(!relative.unwrap().starts_with("relative"));
+    }
+
+    #[test]
+    fn tasks_with_equal_fields_compare_equal() {
+        let task = Task::new("buy milk");
+        assert_eq!(task, task.clone());
+
+        let mut other = Task::new("buy milk");
+        other.created_at = task.created_at;
+        assert_eq!(task, other);
+
+        other.set_done(true);
+        assert_ne!(task, other);
+    }
+
+    /// Runs `body` on a guard that records each save's task names in the returned list.
+    fn saves_made(armed: bool, body: impl FnOnce(&mut AutosaveGuard)) -> Vec<Vec<String>> {
+        let saves = std::cell::RefCell::new(Vec::new());
+        let mut data = SaveData::from_tasks(vec![Task::new("a")]);
+        {
+            let mut guard = AutosaveGuard::with_saver(&mut data, armed, |data| {
+                saves.borrow_mut().push(data.iter().map(|task| task.name.clone()).collect());
+                return Ok(())
+            });
+            body(&mut guard);
+        }
+        return saves.into_inner()
+    }
+
+    #[test]
+    fn leaving_the_scope_saves() {
+        let saves = saves_made(true, |guard| guard.add_task(Task::new("b")));
+        assert_eq!(saves, [["a", "b"]]);
+    }
+
+    #[test]
+    fn unarmed_and_disarmed_guards_do_not_save() {
+        assert!(saves_made(false, |guard| guard.add_task(Task::new("b"))).is_empty());
+        assert!(saves_made(true, |guard| guard.disarm()).is_empty());
+    }
+
+    #[test]
+    fn saving_early_does_not_save_again_on_drop() {
+        let saves = saves_made(true, |guard| {
+            guard.save().unwrap();
+            guard.add_task(Task::new("b"));
+        });
+        assert_eq!(saves, [["a"]]);
+    }
+
+    #[test]
+    fn a_panic_still_saves() {
+        let saves = std::cell::RefCell::new(0);
+        let mut data = SaveData::new();
+        let result = std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| {
+            let mut guard = AutosaveGuard::with_saver(&mut data, true, |_| {
+                *saves.borrow_mut() += 1;
+                return Ok(())
+            });
+            guard.add_task(Task::new("a"));
+            panic!("printing failed");
+        }));
+        assert!(result.is_err());
+        assert_eq!(*saves.borrow(), 1);
+    }
+
+    #[test]
+    fn app_dirs_fall_back_to_xdg_data_home_without_a_home() {
+        let app_dirs = SaveData::app_dirs_from(None, Some(PathBuf::from("/data")), None).unwrap();
+        assert_eq!(app_dirs.data_dir, PathBuf::from("/data/todo"));
+        assert_eq!(app_dirs.config_dir, PathBuf::from("/data/todo"));
+
+        let app_dirs = SaveData::app_dirs_from(None, Some(PathBuf::from("/data")), Some(PathBuf::from("/config"))).unwrap();
+        assert_eq!(app_dirs.config_dir, PathBuf::from("/config/todo"));
+    }
+
+    #[test]
+    fn app_dirs_fail_without_a_home_or_xdg_data_home() {
+        let error = SaveData::app_dirs_from(None, None, None).unwrap_err();
+        assert_eq!(error.kind(), io::ErrorKind::NotFound);
+        assert!(error.to_string().starts_with("no home directory found"));
+    }
+
+    #[test]
+    fn time_to_complete_needs_both_times() {
+        let created = DateTime::parse_from_rfc3339("2024-05-01T12:00:00Z").unwrap().with_timezone(&Utc);
+        let mut task = Task::new("a");
+        task.created_at = Some(created);
+        assert_eq!(task.time_to_complete(), None);
+
+        task.done = true;
+        task.completed_at = Some(created + TimeDelta::hours(3));
+        assert_eq!(task.time_to_complete(), Some(TimeDelta::hours(3)));
+
+        task.created_at = None;
+        assert_eq!(task.time_to_complete(), None);
+    }
+
+    #[test]
+    fn time_to_complete_ignores_completions_before_creation() {
+        let completed = DateTime::parse_from_rfc3339("2024-01-01T12:00:00Z").unwrap().with_timezone(&Utc);
+        let mut task = Task::new("a");
+        task.done = true;
+        task.completed_at = Some(completed);
+        assert_eq!(task.time_to_complete(), None);
+    }
 }
\ No newline at end of file
@@ -0,0 +1,65 @@
+/// How similar two names must be, from 0 to 1, to be offered as a match.
+pub(crate) const FUZZY_THRESHOLD: f64 = 0.7;
+
+/// Levenshtein distance between two strings, counted in characters.
+fn edit_distance(a: &str, b: &str) -> usize {
+    let b: Vec<char> = b.chars().collect();
+    let mut previous: Vec<usize> = (0..=b.len()).collect();
+    let mut current = vec![0; b.len() + 1];
+
+    for (i, a_char) in a.chars().enumerate() {
+        current[0] = i + 1;
+        for (j, b_char) in b.iter().enumerate() {
+            let substitution = previous[j] + usize::from(a_char != *b_char);
+            current[j + 1] = substitution.min(previous[j + 1] + 1).min(current[j] + 1);
+        }
+        std::mem::swap(&mut previous, &mut current);
+    }
+    return previous[b.len()];
+}
+
+/// Similarity of two names from 0 (nothing in common) to 1 (equal), ignoring case.
+pub(crate) fn similarity(a: &str, b: &str) -> f64 {
+    let a = a.to_lowercase();
+    let b = b.to_lowercase();
+    let longest = a.chars().count().max(b.chars().count());
+    if longest == 0 {
+        return 1.0;
+    }
+    return 1.0 - edit_distance(&a, &b) as f64 / longest as f64;
+}
+
+/// Finds the candidate most similar to `query`, if any reaches `FUZZY_THRESHOLD`.
+pub(crate) fn closest_match<'a>(query: &str, candidates: impl IntoIterator<Item = &'a str>) -> Option<&'a str> {
+    return candidates.into_iter()
+        .map(|candidate| (candidate, similarity(query, candidate)))
+        .filter(|(_, score)| *score >= FUZZY_THRESHOLD)
+        .max_by(|(_, a), (_, b)| a.total_cmp(b))
+        .map(|(candidate, _)| candidate);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn similarity_ignores_case() {
+        assert_eq!(similarity("Buy Milk", "buy milk"), 1.0);
+        assert_eq!(similarity("", ""), 1.0);
+        assert_eq!(similarity("abc", "xyz"), 0.0);
+    }
+
+    #[test]
+    fn closest_match_accepts_exactly_the_threshold() {
+        // 3 edits in 10 characters is a similarity of exactly 0.7.
+        assert_eq!(similarity("abcdefghij", "abcdefgxyz"), FUZZY_THRESHOLD);
+        assert_eq!(closest_match("abcdefghij", ["abcdefgxyz"]), Some("abcdefgxyz"));
+        // 4 edits is 0.6, below it.
+        assert_eq!(closest_match("abcdefghij", ["abcdefwxyz"]), None);
+    }
+
+    #[test]
+    fn closest_match_picks_the_most_similar() {
+        assert_eq!(closest_match("by milk", ["buy bread", "buy milk", "bake"]), Some("buy milk"));
+    }
+}
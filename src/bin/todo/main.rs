@@ -1,103 +1,258 @@
+#![allow(clippy::needless_return)]
+
 mod args;
+mod command_error;
+mod command_impl;
 mod db;
+mod fuzzy;
+mod render;
 
-use std::ops::Deref;
+use std::fs;
+use std::io::{self, BufRead, IsTerminal};
+use std::process::exit;
+use std::sync::mpsc;
+use std::thread;
+use std::time::Duration;
 use clap::Parser;
-use args::{TodoArgs,Command};
-use db::{SaveData,Task,get_index};
-use console::Style;
+use args::{TodoArgs,Command,ListCommand};
+use command_error::CommandError;
+use command_impl::{CommandOutcome,handle_add,handle_archive,handle_bump,handle_clear,handle_copy,handle_count,handle_data_path,handle_done_undone,handle_edit,handle_export,handle_first,handle_import,handle_last,handle_list,handle_migrate,handle_next,handle_note,handle_pin,handle_remove,handle_rename,handle_report,handle_show,handle_swap};
+use db::{AutosaveGuard,SaveData};
+use render::{TimeFormat,apply_color_mode,confirm,list_writer,render_diff,render_list,render_outcome,render_porcelain};
+use console::{Style, Term};
+use notify::{Event, RecursiveMode, Watcher};
+
+/// Held by tests that set environment variables, since tests run in parallel.
+#[cfg(test)]
+pub(crate) static ENV_LOCK: std::sync::Mutex<()> = std::sync::Mutex::new(());
+
+/// Reads task names from stdin, one per line, unless stdin is a terminal.
+fn read_piped_tasks() -> Vec<String> {
+    let stdin = io::stdin();
+    if stdin.is_terminal() {
+        return Vec::new();
+    }
+    return read_task_lines(stdin.lock());
+}
+
+/// Reads one task name per line, skipping blank lines.
+fn read_task_lines(reader: impl BufRead) -> Vec<String> {
+    return reader.lines()
+        .map_while(Result::ok)
+        .map(|line| line.trim().to_string())
+        .filter(|line| !line.is_empty())
+        .collect();
+}
+
+/// Renders the list to stdout or the `--output` file.
+fn show_list(data: &SaveData, list_command: &ListCommand, done_style: &Style, quiet: bool) -> Result<(), CommandError> {
+    let page = handle_list(data, list_command)?;
+    let mut out = list_writer(list_command.output.as_deref()).map_err(CommandError::io("open the output file"))?;
+    let written = if list_command.porcelain {
+        render_porcelain(&mut out, &page)
+    } else {
+        render_list(&mut out, &page, done_style, quiet)
+    };
+    return written.map_err(CommandError::io("write the list"));
+}
+
+/// Redraws the list whenever todos.json changes, until interrupted with Ctrl-C.
+fn watch_list(list_command: &ListCommand, done_style: &Style, quiet: bool) -> Result<(), CommandError> {
+    let (app_dirs, data_file_path) = SaveData::get_data_paths().map_err(CommandError::io("find the data folder"))?;
+    fs::create_dir_all(&app_dirs.data_dir).map_err(CommandError::io("create the data folder"))?;
+
+    // Watch the folder rather than the file, since saving renames a new file over it.
+    let (sender, receiver) = mpsc::channel();
+    let mut watcher = notify::recommended_watcher(sender)
+        .map_err(|err| CommandError::io("watch the task list")(io::Error::other(err)))?;
+    watcher.watch(&app_dirs.data_dir, RecursiveMode::NonRecursive)
+        .map_err(|err| CommandError::io("watch the task list")(io::Error::other(err)))?;
+
+    loop {
+        // While another program replaces the file it can be missing or half-written.
+        // Keep the last drawn list then, the next event redraws it.
+        let mut data = SaveData::new();
+        if data_file_path.exists() && data.load_tasks().is_ok() {
+            let _ = Term::stdout().clear_screen();
+            show_list(&data, list_command, done_style, quiet)?;
+        }
+
+        loop {
+            let event: notify::Result<Event> = match receiver.recv() {
+                Ok(event) => event,
+                Err(_) => return Ok(())
+            };
+            // Skip access events, reading the file to redraw it would trigger another redraw.
+            if event.is_ok_and(|event| !event.kind.is_access() && event.paths.contains(&data_file_path)) {
+                break;
+            }
+        }
+        // A save fires several events, so wait for the rest and redraw once.
+        thread::sleep(Duration::from_millis(50));
+        while receiver.try_recv().is_ok() {}
+    }
+}
 
 fn main() {
-    let args = TodoArgs::parse();
-    let mut data = SaveData::new();
+    let mut args = TodoArgs::parse();
+    apply_color_mode(args.color);
+    let time_format = TimeFormat::from_arg(&args.time_format);
+    let mut saved = SaveData::new();
     let done_style = Style::new().strikethrough().green();
 
-    let _ = data.load_tasks();
-
-    match &args.command {
-        Command::Add(command) => {
-            if command.tasks.len() <= 0 {
-                println!("No tasks specified!")
-            } else {
-                let mut success = true;
-                for task in &command.tasks {
-                    let option = get_index(data.get_tasks(), task);
-                    match option {
-                        Some(_) => {
-                            // Task already exists
-                            println!("Task already exists!");
-                            success = false;
-                        }
-                        None => {
-                            let task_obj: Task = Task{name: task.deref().parse().unwrap(), done: false};
-                            data.add_task(task_obj);
-                        }
-                    }
-                }
-                if success {
-                    println!("Successfully added tasks.")
-                }
+    // Handled before loading, so it still finds the file when the file is broken.
+    if let Command::DataPath(command) = &args.command {
+        match handle_data_path(command) {
+            Ok(outcome) => render_outcome(&outcome, &time_format),
+            Err(err) => {
+                err.handle();
+                exit(1);
             }
         }
-        Command::Remove(command) => {
-            if command.tasks.len() <= 0 {
-                println!("No tasks specified!")
-            } else {
-                for task in &command.tasks {
-                    let option = get_index(data.get_tasks(), task);
-                    match option {
-                        Some(index) => {
-                            data.remove_task(index);
-                        }
-                        None => println!("Unable to find specified task!"),
-                    }
-                }
-            }
+        return;
+    }
+
+    // Stop rather than start from an empty list, which the next save would write over the file.
+    if let Err(err) = saved.load_tasks().map_err(CommandError::io("load the task list")) {
+        err.handle();
+        exit(1);
+    }
+
+    // Take task names from stdin when none are given, so `... | todo done` works.
+    match &mut args.command {
+        Command::Remove(command) if command.tasks.is_empty() && command.matching.is_none() => {
+            command.tasks = read_piped_tasks()
         }
-        Command::Done(command) => {
-            if command.tasks.len() <= 0 {
-                println!("No tasks specified!")
-            } else {
-                for task in &command.tasks {
-                    let option = get_index(data.get_tasks(), task);
-                    match option {
-                        Some(index) => {
-                            data.mark_task_done(index);
-                        }
-                        None => println!("Unable to find specified task!"),
-                    }
-                }
-            }
+        Command::Done(command) | Command::Undone(command)
+            if command.tasks.is_empty() && !command.all && command.matching.is_none() => {
+            command.tasks = read_piped_tasks()
         }
-        Command::Undone(command) => {
-            if command.tasks.len() <= 0 {
-                println!("No tasks specified!")
-            } else {
-                for task in &command.tasks {
-                    let option = get_index(data.get_tasks(), task);
-                    match option {
-                        Some(index) => {
-                            data.mark_task_undone(index);
-                        }
-                        None => println!("Unable to find specified task!"),
-                    }
-                }
-            }
+        _ => {}
+    }
+    let tasks_before = saved.get_tasks().to_vec();
+
+    let changes_tasks = match &args.command {
+        Command::Add(_) | Command::Remove(_) | Command::Done(_) | Command::Undone(_)
+            | Command::Clear(_) | Command::Edit | Command::Bump(_) | Command::Swap(_)
+            | Command::Copy(_) | Command::Pin(_) | Command::Unpin(_)
+            | Command::Import(_) | Command::Note(_) | Command::Rename(_)
+            | Command::Migrate => true,
+        Command::Archive(command) => !command.list,
+        _ => false
+    };
+    // Read-only commands don't save, so they can't overwrite changes made by
+    // another todo running at the same time, e.g. `todo list | todo done`.
+    let mut data = AutosaveGuard::new(&mut saved, changes_tasks && !args.dry_run);
+
+    // Only asks when someone can answer, otherwise an unknown name stays an error.
+    let ask_fuzzy = |prompt: &str| io::stdin().is_terminal() && confirm(prompt);
+    let fuzzy = args.fuzzy.then_some(&ask_fuzzy as &dyn Fn(&str) -> bool);
+    let ask_clear = |prompt: &str| {
+        if !Term::stdout().is_term() {
+            return Err(CommandError::ConfirmationRequired);
         }
-        Command::Clear => {
-            data.clear_tasks();
+        return Ok(confirm(prompt));
+    };
+
+    let result = match &args.command {
+        Command::Add(command) => handle_add(&mut data, command),
+        Command::Remove(command) => handle_remove(&mut data, command, args.dry_run, fuzzy),
+        Command::Done(command) => handle_done_undone(&mut data, command, true, fuzzy),
+        Command::Undone(command) => handle_done_undone(&mut data, command, false, fuzzy),
+        Command::Clear(command) => handle_clear(&mut data, command, args.dry_run, ask_clear),
+        Command::Archive(command) => handle_archive(&mut data, command, args.dry_run),
+        Command::Edit => handle_edit(&mut data),
+        Command::Bump(command) => handle_bump(&mut data, command),
+        Command::Pin(command) => handle_pin(&mut data, command, true, fuzzy),
+        Command::Unpin(command) => handle_pin(&mut data, command, false, fuzzy),
+        Command::Rename(command) => handle_rename(&mut data, command, args.dry_run),
+        Command::Swap(command) => handle_swap(&mut data, command),
+        Command::Copy(command) => handle_copy(&mut data, command),
+        Command::Count(command) => handle_count(&data, command),
+        Command::DataPath(_) => unreachable!("data-path is handled before loading"),
+        Command::First(command) => handle_first(&data, command),
+        Command::Last(command) => handle_last(&data, command),
+        Command::Next(command) => handle_next(&data, command),
+        Command::Report => handle_report(&data),
+        Command::Note(command) => handle_note(&mut data, command),
+        Command::Migrate => handle_migrate(&mut data, args.dry_run),
+        Command::Show(command) => handle_show(&data, command),
+        Command::Export(command) => handle_export(&data, command),
+        Command::Import(command) => handle_import(&mut data, command),
+        Command::List(_) => Ok(CommandOutcome::Nothing) // List just shows the tasks, that is below:
+    };
+
+    match result {
+        Ok(outcome) => render_outcome(&outcome, &time_format),
+        // Handlers check every input before changing anything, so a failed command
+        // changed nothing. There is no partial result to save or reprint.
+        Err(err) => {
+            data.disarm();
+            err.handle();
+            exit(1);
         }
-        Command::List => {} // List just shows the tasks, that is below:
     }
 
-    data.save_tasks().unwrap();
+    if let Err(err) = data.save().map_err(CommandError::io("save the task list")) {
+        err.handle();
+        exit(1);
+    }
+
+    // These print output meant for scripts, so it must be the only thing printed.
+    let prints_only_output = match &args.command {
+        Command::Count(_) | Command::First(_) | Command::Last(_)
+            | Command::Next(_) | Command::Report | Command::Export(_) | Command::Show(_) => true,
+        Command::Archive(command) => command.list,
+        _ => false
+    };
+    if prints_only_output {
+        return;
+    }
+    // Migrating only fills in fields the diff doesn't show, so its summary says it all.
+    if let Command::Migrate = args.command {
+        if args.dry_run {
+            println!("\n(dry run, no changes made)");
+        }
+        return;
+    }
 
-    println!("\nCurrent tasks:");
-    for task in data.get_tasks() {
-        if task.done {
-            println!("{}", done_style.apply_to(&task.name))
-        } else {
-            println!("{}", task.name)
+    if changes_tasks {
+        render_diff(&tasks_before, data.get_tasks());
+        if !args.list_after {
+            if args.dry_run {
+                println!("\n(dry run, no changes made)");
+            }
+            return;
         }
     }
+
+    let default_list = ListCommand::default();
+    let list_command = match &args.command {
+        Command::List(command) => command,
+        _ => &default_list
+    };
+    let result = if list_command.watch {
+        watch_list(list_command, &done_style, args.quiet)
+    } else {
+        show_list(&data, list_command, &done_style, args.quiet)
+    };
+    if let Err(err) = result {
+        err.handle();
+        exit(1);
+    }
+
+    if args.dry_run && !list_command.porcelain {
+        println!("\n(dry run, no changes made)");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn piped_task_names_are_read_one_per_line() {
+        let piped = "buy milk\n  walk the dog \n\n\tcall mum\r\n";
+        assert_eq!(read_task_lines(piped.as_bytes()), ["buy milk", "walk the dog", "call mum"]);
+    }
 }
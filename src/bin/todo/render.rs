@@ -0,0 +1,455 @@
+use std::env;
+use std::fs::File;
+use std::io::{self, Write};
+use std::path::Path;
+use chrono::{DateTime, Local, SecondsFormat, TimeDelta, Utc};
+use chrono::format::{Item, StrftimeItems};
+use console::{colors_enabled, set_colors_enabled, set_colors_enabled_stderr, Style};
+use crate::args::ColorMode;
+use crate::command_impl::{CommandOutcome,TaskPage};
+use crate::db::{Task,get_index};
+
+pub(crate) fn apply_color_mode(mode: ColorMode) {
+    match mode {
+        // console already checks for a terminal and NO_COLOR/CLICOLOR on its own.
+        ColorMode::Auto => {}
+        ColorMode::Always => {
+            set_colors_enabled(true);
+            set_colors_enabled_stderr(true);
+        }
+        ColorMode::Never => {
+            set_colors_enabled(false);
+            set_colors_enabled_stderr(false);
+        }
+    }
+}
+
+pub(crate) enum TimeFormat {
+    /// Like "3h ago".
+    Relative,
+    /// RFC 3339 in local time.
+    Iso,
+    /// A parsed strftime pattern.
+    Custom(Vec<Item<'static>>)
+}
+
+impl TimeFormat {
+    /// Parses the `--time-format` value. Invalid strftime patterns fall back to ISO with a warning.
+    pub(crate) fn from_arg(arg: &str) -> TimeFormat {
+        match arg {
+            "relative" => TimeFormat::Relative,
+            "iso" => TimeFormat::Iso,
+            pattern => match StrftimeItems::new(pattern).parse_to_owned() {
+                Ok(items) => TimeFormat::Custom(items),
+                Err(_) => {
+                    eprintln!("Invalid time format \"{}\", using ISO times instead.", pattern);
+                    TimeFormat::Iso
+                }
+            }
+        }
+    }
+}
+
+fn format_relative(time: DateTime<Utc>) -> String {
+    let seconds = Utc::now().signed_duration_since(time).num_seconds();
+    let (amount, unit) = match seconds.abs() {
+        0..=59 => return "just now".to_string(),
+        60..=3_599 => (seconds.abs() / 60, "m"),
+        3_600..=86_399 => (seconds.abs() / 3_600, "h"),
+        _ => (seconds.abs() / 86_400, "d")
+    };
+    return if seconds < 0 {
+        format!("in {}{}", amount, unit)
+    } else {
+        format!("{}{} ago", amount, unit)
+    }
+}
+
+/// Formats a duration with its two largest units, like "2d 3h" or "5m".
+pub(crate) fn format_duration(duration: TimeDelta) -> String {
+    let minutes = duration.num_minutes().max(0);
+    let (days, hours, minutes) = (minutes / 1_440, minutes / 60 % 24, minutes % 60);
+    return if days > 0 {
+        format!("{}d {}h", days, hours)
+    } else if hours > 0 {
+        format!("{}h {}m", hours, minutes)
+    } else if minutes > 0 {
+        format!("{}m", minutes)
+    } else {
+        "under a minute".to_string()
+    }
+}
+
+/// A task's name padded to `width` and how long it took, or a dash when that's
+/// unknown, e.g. for tasks from older versions that have no creation time.
+fn report_line(task: &Task, width: usize) -> String {
+    let took = task.time_to_complete().map_or("\u{2014}".to_string(), format_duration);
+    return format!("{:width$}  {}", task.name, took, width = width)
+}
+
+pub(crate) fn format_time(time: DateTime<Utc>, format: &TimeFormat) -> String {
+    let local_time = time.with_timezone(&Local);
+    match format {
+        TimeFormat::Relative => format_relative(time),
+        TimeFormat::Iso => local_time.to_rfc3339_opts(SecondsFormat::Secs, false),
+        TimeFormat::Custom(items) => local_time.format_with_items(items.iter()).to_string()
+    }
+}
+
+/// Prints the result of a command, before the task list is shown.
+pub(crate) fn render_outcome(outcome: &CommandOutcome, time_format: &TimeFormat) {
+    match outcome {
+        CommandOutcome::Nothing => {}
+        CommandOutcome::NoTasksSpecified => println!("No tasks specified!"),
+        CommandOutcome::Added { existing } => {
+            if existing.is_empty() {
+                println!("Successfully added tasks.")
+            }
+            for _ in existing {
+                println!("Task already exists!");
+            }
+        }
+        CommandOutcome::WouldRemove(names) => {
+            for name in names {
+                println!("Would remove: {}", name);
+            }
+        }
+        CommandOutcome::Aborted => println!("Aborted, no changes made."),
+        CommandOutcome::UnconfirmedMatches { names, action } => {
+            println!("Found {} matching tasks:", names.len());
+            for name in names {
+                println!("  {}", name);
+            }
+            println!("Pass --yes to {}.", action);
+        }
+        CommandOutcome::WouldArchive(names) => {
+            for name in names {
+                println!("Would archive: {}", name);
+            }
+        }
+        CommandOutcome::Archived(count) => println!("Archived {} tasks.", count),
+        CommandOutcome::ArchiveList(archive) => {
+            println!("Archived tasks:");
+            for archived in archive {
+                println!("{} (archived {})", archived.task.name, format_time(archived.archived_at, time_format));
+            }
+        }
+        CommandOutcome::EditUnchanged { editor_failed: true } => {
+            println!("Editor exited with an error, no changes made.")
+        }
+        CommandOutcome::EditUnchanged { editor_failed: false } => println!("No changes made."),
+        CommandOutcome::Edited { added, removed, toggled } => {
+            println!("Added {}, removed {}, toggled {} tasks.", added, removed, toggled)
+        }
+        CommandOutcome::Count(count) => println!("{}", count),
+        CommandOutcome::DataPath(path) => println!("{}", path.display()),
+        CommandOutcome::Report(tasks) if tasks.is_empty() => println!("No done tasks"),
+        CommandOutcome::Report(tasks) => {
+            let width = tasks.iter().map(|task| task.name.chars().count()).max().unwrap_or(0);
+            for task in tasks {
+                println!("{}", report_line(task, width));
+            }
+        }
+        CommandOutcome::Renames { renames, conflicts, applied } => {
+            let verb = if *applied { "Renamed" } else { "Would rename" };
+            for (old_name, new_name) in renames {
+                println!("{}: {} -> {}", verb, old_name, new_name);
+            }
+            for (old_name, new_name) in conflicts {
+                println!("Skipped: {} -> {}, the name is taken", old_name, new_name);
+            }
+            if renames.is_empty() && conflicts.is_empty() {
+                println!("No task names match.");
+            } else if !applied && !renames.is_empty() {
+                println!("Pass --yes to rename.");
+            }
+        }
+        CommandOutcome::Migrated { count: 0, backup: None } => println!("Already up to date, nothing to migrate."),
+        CommandOutcome::Migrated { count, backup: Some(backup) } => {
+            println!("Migrated {} tasks. The original file was backed up to {}", count, backup.display())
+        }
+        CommandOutcome::Migrated { count, backup: None } => println!("Would migrate {} tasks.", count),
+        CommandOutcome::Show(task) => {
+            println!("{} ({})", task.name, if task.done { "done" } else { "not done" });
+            if task.notes.is_empty() {
+                println!("No notes.");
+            }
+            for note in &task.notes {
+                println!("  {}: {}", format_time(note.added_at, time_format), note.text);
+            }
+        }
+        CommandOutcome::Export(contents) => println!("{}", contents),
+        CommandOutcome::Exported { count, path } => println!("Exported {} tasks to {}", count, path.display()),
+        CommandOutcome::Imported { added, skipped } => {
+            println!("Imported {} tasks, skipped {} already in the list.", added, skipped)
+        }
+        CommandOutcome::Next(None) => println!("Nothing to do \u{1F389}"),
+        CommandOutcome::Next(Some(name)) => println!("{}", name),
+        CommandOutcome::Ends(tasks) if tasks.is_empty() => println!("No tasks"),
+        CommandOutcome::Ends(tasks) => {
+            for task in tasks {
+                println!("{}", task.name);
+            }
+        }
+    }
+}
+
+/// Asks a yes/no question on stdout and reads the answer from stdin. Anything but "y" or "yes" is no.
+pub(crate) fn confirm(prompt: &str) -> bool {
+    print!("{} [y/N] ", prompt);
+    let _ = io::stdout().flush();
+
+    let mut answer = String::new();
+    if io::stdin().read_line(&mut answer).is_err() {
+        return false;
+    }
+    let answer = answer.trim().to_lowercase();
+    return answer == "y" || answer == "yes";
+}
+
+/// Reads a line prefix from an environment variable, adding a space after it.
+fn marker_prefix(name: &str) -> String {
+    match env::var(name) {
+        Ok(marker) if !marker.is_empty() => format!("{} ", marker),
+        _ => String::new()
+    }
+}
+
+/// Looks up the style for a category in `TODO_CATEGORY_COLORS`, which holds
+/// comma-separated `category=style` pairs like `work=blue,home=green.bold`.
+/// Tasks without a category, or with one not in the table, get the default style.
+pub(crate) fn category_style(category: Option<&str>) -> Style {
+    let (Some(category), Ok(table)) = (category, env::var("TODO_CATEGORY_COLORS")) else {
+        return Style::new()
+    };
+    return table.split(',')
+        .filter_map(|pair| pair.split_once('='))
+        .find(|(name, _)| name.trim() == category)
+        .map(|(_, style)| Style::from_dotted_str(style.trim()))
+        .unwrap_or_default();
+}
+
+/// Picks where the list is written: the `--output` file, or stdout.
+/// Writing to a file turns colors off, so the file has no escape codes.
+pub(crate) fn list_writer(output: Option<&Path>) -> io::Result<Box<dyn Write>> {
+    return match output {
+        Some(path) => {
+            set_colors_enabled(false);
+            Ok(Box::new(File::create(path)?))
+        }
+        None => Ok(Box::new(io::stdout()))
+    }
+}
+
+pub(crate) fn render_list(out: &mut dyn Write, page: &TaskPage, done_style: &Style, quiet: bool) -> io::Result<()> {
+    // Optional prefixes for terminals where the done style is hard to see.
+    let done_marker = marker_prefix("TODO_DONE_MARKER");
+    let pending_marker = marker_prefix("TODO_PENDING_MARKER");
+
+    writeln!(out, "\nCurrent tasks:")?;
+    for (position, (index, task)) in page.tasks.iter().enumerate() {
+        // Pinned tasks come first, so a divider goes where they end.
+        if position > 0 && page.tasks[position - 1].1.pinned && !task.pinned {
+            writeln!(out, "---")?;
+        }
+        if task.done {
+            writeln!(out, "{}{}", done_marker, done_style.apply_to(&task.name))?;
+        } else {
+            let style = category_style(task.category.as_deref());
+            let blocked = if page.blocked.contains(index) { " [blocked]" } else { "" };
+            writeln!(out, "{}{}{}", pending_marker, style.apply_to(&task.name), blocked)?;
+        }
+    }
+
+    if page.paged {
+        if page.tasks.is_empty() {
+            writeln!(out, "showing 0 of {}", page.matching)?;
+        } else {
+            writeln!(out, "showing {}-{} of {}", page.start + 1, page.start + page.tasks.len(), page.matching)?;
+        }
+    }
+
+    if !quiet {
+        writeln!(out, "{} done / {} total", page.done_total, page.total)?;
+        // Without colors the strike-through isn't shown, so there's nothing to explain.
+        if colors_enabled() && page.done_total > 0 {
+            writeln!(out, "{} = done", done_style.apply_to("struck through"))?;
+        }
+    }
+    return out.flush();
+}
+
+/// Escapes a task name so it fits in one tab-separated field.
+fn escape_porcelain(name: &str) -> String {
+    return name.replace('\\', "\\\\")
+        .replace('\t', "\\t")
+        .replace('\n', "\\n")
+        .replace('\r', "\\r")
+}
+
+/// Prints the list as `done<TAB>name<TAB>id` lines for scripts. `id` is the
+/// 1-based position accepted by `remove`, `done` and `undone`, whatever the sort.
+/// This format must stay stable, so it never uses colors, headers or footers.
+pub(crate) fn render_porcelain(out: &mut dyn Write, page: &TaskPage) -> io::Result<()> {
+    for ((_, task), position) in page.tasks.iter().zip(&page.positions) {
+        writeln!(out, "{}\t{}\t{}", task.done, escape_porcelain(&task.name), position)?;
+    }
+    return out.flush();
+}
+
+/// Prints what a command changed: `+` for added tasks, `-` for removed tasks
+/// and `~` for tasks whose done or pinned state flipped or that got a note.
+pub(crate) fn render_diff(before: &[Task], after: &[Task]) {
+    let added_style = Style::new().green();
+    let removed_style = Style::new().red();
+    let toggled_style = Style::new().yellow();
+
+    let mut changed = false;
+    for task in before {
+        if get_index(after, &task.name).is_none() {
+            println!("{}", removed_style.apply_to(format!("- {}", task.name)));
+            changed = true;
+        }
+    }
+    for task in after {
+        match get_index(before, &task.name) {
+            None => {
+                println!("{}", added_style.apply_to(format!("+ {}", task.name)));
+                changed = true;
+            }
+            Some(index) => {
+                let mut states = Vec::new();
+                if before[index].done != task.done {
+                    states.push(if task.done { "done" } else { "not done" });
+                }
+                if before[index].pinned != task.pinned {
+                    states.push(if task.pinned { "pinned" } else { "unpinned" });
+                }
+                if before[index].notes.len() != task.notes.len() {
+                    states.push("note added");
+                }
+                if !states.is_empty() {
+                    println!("{}", toggled_style.apply_to(format!("~ {} ({})", task.name, states.join(", "))));
+                    changed = true;
+                }
+            }
+        }
+    }
+
+    if !changed {
+        let reordered = before.iter().map(|task| &task.name).ne(after.iter().map(|task| &task.name));
+        println!("{}", if reordered { "Task order changed." } else { "No changes." });
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::args::ListCommand;
+    use crate::command_impl::handle_list;
+    use crate::db::SaveData;
+
+    #[test]
+    fn porcelain_output_is_exact() {
+        let mut data = SaveData::from_tasks(vec![Task::new("tab\there"), Task::new("two\nlines"), Task::new("back\\slash")]);
+        data.mark_task_done(1);
+        let page = handle_list(&data, &ListCommand::default()).unwrap();
+
+        let mut out = Vec::new();
+        render_porcelain(&mut out, &page).unwrap();
+        assert_eq!(out, b"false\ttab\\there\t1\ntrue\ttwo\\nlines\t2\nfalse\tback\\\\slash\t3\n");
+    }
+
+    fn list_output(data: &SaveData, quiet: bool) -> String {
+        let page = handle_list(data, &ListCommand::default()).unwrap();
+        let mut out = Vec::new();
+        render_list(&mut out, &page, &Style::new().strikethrough(), quiet).unwrap();
+        return String::from_utf8(out).unwrap()
+    }
+
+    #[test]
+    fn footer_counts_match_the_list() {
+        let mut data = SaveData::from_tasks(vec![Task::new("a"), Task::new("b"), Task::new("c")]);
+        data.mark_task_done(1);
+        assert!(list_output(&data, false).lines().any(|line| line == "1 done / 3 total"));
+        assert!(!list_output(&data, true).contains("total"));
+    }
+
+    #[test]
+    fn markers_prefix_tasks_when_configured() {
+        let _lock = crate::ENV_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut data = SaveData::from_tasks(vec![Task::new("a"), Task::new("b")]);
+        data.mark_task_done(1);
+
+        let unset = list_output(&data, true);
+        env::set_var("TODO_DONE_MARKER", "[x]");
+        env::set_var("TODO_PENDING_MARKER", "[ ]");
+        let set = list_output(&data, true);
+        env::remove_var("TODO_DONE_MARKER");
+        env::remove_var("TODO_PENDING_MARKER");
+
+        let lines = |output: &str| -> Vec<String> {
+            return console::strip_ansi_codes(output).lines().skip(2).map(str::to_string).collect()
+        };
+        assert_eq!(lines(&unset), ["a", "b"]);
+        assert_eq!(lines(&set), ["[ ] a", "[x] b"]);
+    }
+
+    #[test]
+    fn list_output_file_has_no_escape_codes() {
+        // Colors are global, so keep the other rendering tests out while they're on.
+        let _lock = crate::ENV_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        let mut data = SaveData::from_tasks(vec![Task::new("a"), Task::new("b")]);
+        data.mark_task_done(0);
+        let page = handle_list(&data, &ListCommand::default()).unwrap();
+        let path = env::temp_dir().join(format!("todo-output-test-{}.txt", std::process::id()));
+
+        set_colors_enabled(true);
+        let mut out = list_writer(Some(&path)).unwrap();
+        render_list(&mut out, &page, &Style::new().strikethrough().green(), false).unwrap();
+        drop(out);
+        let written = std::fs::read(&path).unwrap();
+        let _ = std::fs::remove_file(&path);
+
+        assert!(!written.contains(&0x1b), "{:?}", String::from_utf8_lossy(&written));
+        assert!(String::from_utf8(written).unwrap().contains("1 done / 2 total"));
+    }
+
+    #[test]
+    fn known_categories_get_their_own_style() {
+        let _lock = crate::ENV_LOCK.lock().unwrap_or_else(|poisoned| poisoned.into_inner());
+        env::set_var("TODO_CATEGORY_COLORS", "work=blue, home = green.bold");
+        let styled = |category: Option<&str>| -> String {
+            return category_style(category).force_styling(true).apply_to("task").to_string()
+        };
+        let (work, home, unknown, none) = (styled(Some("work")), styled(Some("home")), styled(Some("garden")), styled(None));
+        env::remove_var("TODO_CATEGORY_COLORS");
+
+        assert_eq!(work, Style::new().blue().force_styling(true).apply_to("task").to_string());
+        assert_eq!(home, Style::new().green().bold().force_styling(true).apply_to("task").to_string());
+        assert_ne!(work, none);
+        assert_eq!(unknown, none);
+        assert_eq!(none, "task");
+    }
+
+    #[test]
+    fn durations_use_the_two_largest_units() {
+        assert_eq!(format_duration(TimeDelta::seconds(30)), "under a minute");
+        assert_eq!(format_duration(TimeDelta::minutes(5)), "5m");
+        assert_eq!(format_duration(TimeDelta::minutes(125)), "2h 5m");
+        assert_eq!(format_duration(TimeDelta::hours(50) + TimeDelta::minutes(10)), "2d 2h");
+        assert_eq!(format_duration(TimeDelta::minutes(-5)), "under a minute");
+    }
+
+
+    #[test]
+    fn report_lines_show_a_dash_for_unknown_durations() {
+        let mut task = Task::new("paid");
+        task.done = true;
+        task.completed_at = Some(DateTime::parse_from_rfc3339("2024-01-01T12:00:00Z").unwrap().with_timezone(&Utc));
+        assert_eq!(report_line(&task, 6), "paid    \u{2014}");
+
+        task.created_at = Some(task.completed_at.unwrap() - TimeDelta::hours(2));
+        assert_eq!(report_line(&task, 4), "paid  2h 0m");
+    }
+}